@@ -0,0 +1,142 @@
+// Run with `cargo bench`. Pass a filter to run just one group, eg. `cargo bench -- meshing`.
+
+use bevy::{prelude::*, ecs::system::CommandQueue, math::Vec3A};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, black_box};
+use ndarray::Array3;
+
+use zombies_gold::{
+    map::{LoadedChunks, Block, BlockType, SetBlockEvent, SetBlockShape, MapPlugin, generate_greedy_mesh, hash_position, set_block_chunk},
+    physics::{AabbCollider, Velocity, step_velocity},
+    storage::ChunkStorage,
+    coords::CHUNK_SIZE,
+};
+
+
+// Consts
+const SEED: u64 = 42;
+// One frame at 60fps
+const STEP: f32 = 1.0 / 60.0;
+
+// Benches
+fn meshing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("meshing");
+
+    let chunks: [(&str, fn(IVec3) -> BlockType); 3] = [
+        ("flat", flat),
+        ("noisy", noisy),
+        // Every face of every block is visible and nothing can merge, so this is as bad as it gets.
+        ("checkerboard", checkerboard),
+    ];
+
+    for (name, fill) in chunks {
+        let mut world = World::new();
+        let loaded_chunks = build_chunks(&mut world, &[IVec3::ZERO], fill);
+
+        group.bench_function(name, |b| {
+            b.iter(|| generate_greedy_mesh(black_box(&loaded_chunks), IVec3::ZERO))
+        });
+    }
+
+    group.finish();
+}
+
+/// One physics step of a moving collider, the same per-axis checks and moves apply_velocity does every frame.
+fn collision(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step_velocity");
+
+    // A floor of chunks for the collider to hit
+    let mut world = World::new();
+    let indices: Vec<IVec3> = (-1..=1).flat_map(|x| (-1..=1).flat_map(move |z| [IVec3::new(x, -1, z), IVec3::new(x, 0, z)])).collect();
+    let loaded_chunks = build_chunks(&mut world, &indices, flat);
+
+    // Player sized, a couple of blocks above the dirt so the faster steps reach it
+    let aabb = AabbCollider::new(Vec3A::new(0.4, 1.8, 0.4));
+    let start = Transform::from_translation(Vec3::new(0.0, CHUNK_SIZE as f32 / 2.0 + 2.45, 0.0));
+
+    // Blocks moved in one step, from walking up to falling very fast
+    for speed in [0.1, 1.0, 4.0, 16.0] {
+        let velocity = Velocity(Vec3::new(0.5, -1.0, 0.25).normalize() * speed / STEP);
+
+        group.bench_with_input(BenchmarkId::from_parameter(speed), &velocity, |b, velocity| {
+            b.iter(|| {
+                let mut velocity = *velocity;
+                let mut transform = start;
+                step_velocity(&mut velocity, &mut transform, Some(&aabb), None, black_box(STEP), black_box(&loaded_chunks));
+                transform
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn set_blocks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set_block_events");
+
+    for count in [64, 1024, CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE] {
+        let mut app = App::new();
+        app
+         .add_plugin(MapPlugin)
+         .add_system(set_block_chunk);
+
+        app.world.resource_mut::<Events<SetBlockEvent>>().send(SetBlockEvent { shape: SetBlockShape::Chunk(IVec3::ZERO), block: Block::new(BlockType::Air) });
+        app.update();
+
+        // Alternate what gets placed so every event actually changes a block
+        let mut placing = BlockType::Dirt;
+
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, count| {
+            b.iter(|| {
+                placing = if placing == BlockType::Dirt {BlockType::Stone} else {BlockType::Dirt};
+
+                {
+                    let mut events = app.world.resource_mut::<Events<SetBlockEvent>>();
+                    for i in 0..*count {
+                        let position = IVec3::new((i % CHUNK_SIZE) as i32, (i / CHUNK_SIZE % CHUNK_SIZE) as i32, (i / (CHUNK_SIZE * CHUNK_SIZE)) as i32);
+                        events.send(SetBlockEvent { shape: SetBlockShape::Block(position), block: Block::new(placing) });
+                    }
+                }
+
+                app.update();
+            })
+        });
+    }
+
+    group.finish();
+}
+
+// Helper functions
+/// Loads chunks filled by `fill`, which is given world positions.
+fn build_chunks(world: &mut World, indices: &[IVec3], fill: fn(IVec3) -> BlockType) -> LoadedChunks {
+    let mut loaded_chunks = LoadedChunks::default();
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+
+    for index in indices {
+        let origin = *index * CHUNK_SIZE as i32;
+        let blocks = Array3::from_shape_fn((CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE), |(x, y, z)| {
+            Block::new(fill(origin + IVec3::new(x as i32, y as i32, z as i32)))
+        });
+        loaded_chunks.load_chunk(*index, ChunkStorage::from_dense(&blocks), &mut commands);
+    }
+
+    queue.apply(world);
+    loaded_chunks
+}
+
+/// Dirt up to the middle of chunk 0, air above.
+fn flat(position: IVec3) -> BlockType {
+    if position.y < CHUNK_SIZE as i32 / 2 {BlockType::Dirt} else {BlockType::Air}
+}
+
+/// Roughly half dirt, half air, scattered at random.
+fn noisy(position: IVec3) -> BlockType {
+    if hash_position(SEED, position) % 2 == 0 {BlockType::Dirt} else {BlockType::Air}
+}
+
+fn checkerboard(position: IVec3) -> BlockType {
+    if (position.x + position.y + position.z).rem_euclid(2) == 0 {BlockType::Dirt} else {BlockType::Air}
+}
+
+criterion_group!(benches, meshing, collision, set_blocks);
+criterion_main!(benches);
@@ -1,75 +1,306 @@
 use bevy::{prelude::*, input::mouse::MouseMotion};
 use leafwing_input_manager::{Actionlike, prelude::ActionState};
+use serde::{Serialize, Deserialize};
 
-use crate::physics::Velocity;
+use crate::{physics::{Velocity, Submerged, AabbCollider, StepTime, SWIM_THRESHOLD}, keybinds::{Keybinds, apply_deadzone}, map::LoadedChunks, camera::CameraController, replay::Playback};
 
 const SPEED: f32 = 8.;
 const ACCELERATION: f32 = 2.;
+const SWIM_SPEED: f32 = 3.;
+
+const CROUCH_SPEED_MULT: f32 = 0.4;
+const SPRINT_SPEED_MULT: f32 = 1.6;
+// How much shorter our collider (and how much lower our camera) gets while crouching.
+const CROUCH_HEIGHT_DROP: f32 = 0.3;
+
+// Stamina per second
+const SPRINT_DRAIN: f32 = 20.0;
+const STAMINA_RECOVERY: f32 = 12.0;
+// Once exhausted we can't sprint until stamina is back above this fraction of max.
+const EXHAUSTION_RECOVERY: f32 = 0.25;
 
 // Systems
 pub fn process_actions(
-    mut windows: ResMut<Windows>,
-    time: Res<Time>,
+    windows: Res<Windows>,
+    time: Res<StepTime>,
+    keybinds: Res<Keybinds>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    playback: Option<Res<Playback>>,
+    mut analog: ResMut<AnalogInput>,
 
     mut motion_evr: EventReader<MouseMotion>,
 
-    mut camera_query: Query<(&mut Transform), (With<Camera>, With<Parent>)>,
-    mut query: Query<(&Children, &ActionState<Action>, &mut Velocity, &mut Transform), Without<Camera>>
+    mut camera_query: Query<&mut CameraController>,
+    mut query: Query<(&Children, &ActionState<Action>, &mut Velocity, &mut Transform, Option<&Submerged>, Option<&Stance>, Option<&mut MoveIntent>)>
 ) {
 
-    let sensitivity_mult = 0.005;
-    let window = windows.get_primary_mut().unwrap();
+    let sensitivity_mult = keybinds.mouse_sensitivity;
+    // There's no window when running headless, like in the tests, so act as if it's unfocused.
+    let (cursor_locked, focused) = windows.get_primary().map_or((false, false), |window| (window.cursor_locked(), window.is_focused()));
+
+    // Sticks, with the deadzone already applied.
+    // Only the first gamepad, the same one the InputMap listens to, see keybinds::apply_keybinds.
+    let (left_stick, right_stick) = match gamepads.iter().next() {
+        Some(gamepad) => {
+            let stick = |x, y| Vec2::new(
+                axes.get(GamepadAxis(*gamepad, x)).unwrap_or(0.0),
+                axes.get(GamepadAxis(*gamepad, y)).unwrap_or(0.0),
+            );
+
+            (
+                apply_deadzone(stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY), keybinds.stick_deadzone),
+                apply_deadzone(stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY), keybinds.stick_deadzone),
+            )
+        }
+        None => (Vec2::ZERO, Vec2::ZERO),
+    };
 
-    for (cameras, action_state, mut velocity, mut transform) in query.iter_mut() {
-        if window.cursor_locked() && window.is_focused() {
+    // A replay stands in for the mouse and sticks while it plays back.
+    if playback.is_none() {
+        // Yaw and pitch to turn by this frame
+        let mut look = Vec2::ZERO;
+
+        if cursor_locked && focused {
             for ev in motion_evr.iter() {
-                transform.rotate(Quat::from_rotation_y(-ev.delta.x * sensitivity_mult));
-                for camera in cameras.iter() {
-                    if let Ok(mut camera_transform) = camera_query.get_mut(*camera) {
-                        camera_transform.rotate(Quat::from_rotation_x(-ev.delta.y * sensitivity_mult));
-                        
-                        //camera_transform.rotation.x - 
-                        let lock_val = camera_transform.rotation.x.clamp(-0.523599, 0.523599) - camera_transform.rotation.x;
-                        camera_transform.rotate(Quat::from_rotation_x(lock_val));
-                    }
+                look -= ev.delta * sensitivity_mult;
+            }
+        }
+
+        if focused {
+            look += Vec2::new(-right_stick.x, right_stick.y) * keybinds.stick_sensitivity * time.delta_seconds();
+        }
+
+        *analog = AnalogInput { look, stick: left_stick };
+    }
+
+    for (cameras, action_state, mut velocity, mut transform, opt_submerged, opt_stance, opt_intent) in query.iter_mut() {
+        for camera in cameras.iter() {
+            if let Ok(mut controller) = camera_query.get_mut(*camera) {
+                controller.turn(analog.look.x, analog.look.y);
+                transform.rotation = Quat::from_rotation_y(controller.yaw);
+            }
+        }
+
+        let intent = MoveIntent {
+            forward: action_state.pressed(Action::WalkForward),
+            backward: action_state.pressed(Action::WalkBackward),
+            left: action_state.pressed(Action::StrafeLeft),
+            right: action_state.pressed(Action::StrafeRight),
+            jump: action_state.pressed(Action::Jump),
+            jump_started: action_state.just_pressed(Action::Jump),
+            descend: action_state.pressed(Action::Crouch),
+            stick: analog.stick,
+            crouching: opt_stance.map_or(false, |stance| stance.crouching),
+            sprinting: opt_stance.map_or(false, |stance| stance.sprinting),
+        };
+
+        let swimming = opt_submerged.map_or(false, |submerged| submerged.amount > SWIM_THRESHOLD);
+        walk(&intent, &transform, &mut velocity, swimming, time.delta_seconds());
+
+        // Kept around for anything that needs to know what we asked for this frame, like the network client.
+        if let Some(mut stored_intent) = opt_intent {
+            *stored_intent = intent;
+        }
+    }
+}
+
+/// Handles crouching and sprinting. Runs before process_actions so the speed it uses is up to date.
+pub fn update_stance(
+    time: Res<StepTime>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    mut camera_query: Query<&mut CameraController>,
+    mut query: Query<(&Children, &ActionState<Action>, &Transform, &mut AabbCollider, &mut Stance, Option<&mut Stamina>, Option<&Submerged>)>
+) {
+    for (cameras, action_state, transform, mut aabb, mut stance, opt_stamina, opt_submerged) in query.iter_mut() {
+        let swimming = opt_submerged.map_or(false, |submerged| submerged.amount > SWIM_THRESHOLD);
+        let wants_crouch = action_state.pressed(Action::Crouch) && !swimming;
+
+        let mut height_change = 0.0;
+
+        if wants_crouch && !stance.crouching {
+            stance.crouching = true;
+            height_change = -CROUCH_HEIGHT_DROP;
+        }
+        else if !wants_crouch && stance.crouching {
+            // Only stand up if there's room above our head.
+            let mut standing = AabbCollider::add_location(transform.translation, &aabb);
+            standing.max.y += CROUCH_HEIGHT_DROP;
+
+            if !loaded_chunks.aabb_obstructed(standing) {
+                stance.crouching = false;
+                height_change = CROUCH_HEIGHT_DROP;
+            }
+        }
+
+        // Our feet stay where they are, only the top of the collider and the camera move.
+        if height_change != 0.0 {
+            aabb.max.y += height_change;
+            for camera in cameras.iter() {
+                if let Ok(mut controller) = camera_query.get_mut(*camera) {
+                    controller.eye_height += height_change;
                 }
             }
         }
 
-        
-        if action_state.just_pressed(Action::Jump) {
-            **velocity += Vec3::new(0., 5., 0.); 
+        let moving = action_state.pressed(Action::WalkForward) || action_state.pressed(Action::WalkBackward) ||
+                     action_state.pressed(Action::StrafeLeft) || action_state.pressed(Action::StrafeRight);
+
+        stance.sprinting = false;
+
+        if let Some(mut stamina) = opt_stamina {
+            if action_state.pressed(Action::Sprint) && moving && !stance.crouching && !stamina.exhausted {
+                stance.sprinting = true;
+                stamina.current = (stamina.current - SPRINT_DRAIN * time.delta_seconds()).max(0.0);
+                if stamina.current <= 0.0 {
+                    stamina.exhausted = true;
+                }
+            }
+            else {
+                stamina.current = (stamina.current + STAMINA_RECOVERY * time.delta_seconds()).min(stamina.max);
+                if stamina.current >= stamina.max * EXHAUSTION_RECOVERY {
+                    stamina.exhausted = false;
+                }
+            }
         }
+    }
+}
+
+/// Stops crouching actors from walking off ledges. Runs after process_actions, before physics.
+pub fn edge_protection(
+    time: Res<StepTime>,
+    loaded_chunks: Res<LoadedChunks>,
 
-        let mut direction = Vec3::default();
-        if action_state.pressed(Action::WalkForward) {
-            direction += -transform.local_z();
+    mut query: Query<(&Stance, &mut Velocity, &Transform, &AabbCollider)>,
+) {
+    for (stance, mut velocity, transform, aabb) in query.iter_mut() {
+        if !stance.crouching || velocity.y > 0.0 {
+            continue;
         }
-        else if action_state.pressed(Action::WalkBackward) {
-            direction += transform.local_z();
+
+        let current = AabbCollider::add_location(transform.translation, aabb);
+        if !loaded_chunks.aabb_grounded(current) {
+            continue;
         }
 
-        if action_state.pressed(Action::StrafeLeft) {
-            direction += -transform.local_x();
+        // Each axis is checked on its own so we can still slide along the edge.
+        for axis in [0, 2] {
+            let mut step = Vec3::ZERO;
+            step[axis] = velocity[axis] * time.delta_seconds();
+
+            let next = AabbCollider::add_location(transform.translation + step, aabb);
+            if !loaded_chunks.aabb_grounded(next) {
+                velocity[axis] = 0.0;
+            }
         }
-        else if action_state.pressed(Action::StrafeRight) {
-            direction += transform.local_x();
+    }
+}
+
+// Helper functions
+/// Steers an actor's velocity towards where it wants to go. Shared by local input and the server's remote players.
+pub fn walk(intent: &MoveIntent, transform: &Transform, velocity: &mut Velocity, swimming: bool, dt: f32) {
+    if swimming {
+        // Swimming steers our vertical speed towards a target instead of jumping.
+        let mut target = velocity.y;
+        if intent.jump {
+            target = SWIM_SPEED;
         }
-        
-        if direction != Vec3::default() {
-            let mut velocity_add = ((direction.normalize_or_zero()*SPEED).lerp(**velocity, 0.0) - **velocity) * time.delta_seconds();
-            velocity_add.y = 0.;
-            **velocity += velocity_add;
-        } else {
-            let mut velocity_add = (velocity.lerp(Vec3::default(), 1.0) - **velocity) * time.delta_seconds();
-            velocity_add.y = 0.;
-            **velocity += velocity_add;
+        else if intent.descend {
+            target = -SWIM_SPEED;
         }
+        velocity.y += (target - velocity.y) * (ACCELERATION * dt).min(1.0);
+    }
+    else if intent.jump_started {
+        **velocity += Vec3::new(0., 5., 0.); 
+    }
+
+    let mut direction = Vec3::default();
+    if intent.forward {
+        direction += -transform.local_z();
+    }
+    else if intent.backward {
+        direction += transform.local_z();
+    }
+
+    if intent.left {
+        direction += -transform.local_x();
+    }
+    else if intent.right {
+        direction += transform.local_x();
+    }
+
+    direction += -transform.local_z() * intent.stick.y + transform.local_x() * intent.stick.x;
+    
+    let speed = if intent.crouching {
+        SPEED * CROUCH_SPEED_MULT
+    }
+    else if intent.sprinting {
+        SPEED * SPRINT_SPEED_MULT
     }
+    else {
+        SPEED
+    };
+
+    if direction != Vec3::default() {
+        let mut velocity_add = ((direction.normalize_or_zero()*speed).lerp(**velocity, 0.0) - **velocity) * dt;
+        velocity_add.y = 0.;
+        **velocity += velocity_add;
+    } else {
+        let mut velocity_add = (velocity.lerp(Vec3::default(), 1.0) - **velocity) * dt;
+        velocity_add.y = 0.;
+        **velocity += velocity_add;
+    }
+}
+
+// Components
+/// What an actor's input asked for on its last frame of movement.
+#[derive(Component, Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MoveIntent {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+    pub jump: bool,
+    pub jump_started: bool,
+    // Swim down
+    pub descend: bool,
+    // Left stick, with the deadzone already applied
+    pub stick: Vec2,
+    pub crouching: bool,
+    pub sprinting: bool,
+}
+
+#[derive(Component, Default, Clone, Copy, Debug)]
+pub struct Stance {
+    pub crouching: bool,
+    pub sprinting: bool,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    pub exhausted: bool,
+}
+impl Stamina {
+    pub fn new (max: f32) -> Self {
+        Self {current: max, max, exhausted: false}
+    }
+}
+
+// Resources
+/// The mouse and stick input process_actions used this frame, which ActionState doesn't hold. Kept so replays can record it.
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct AnalogInput {
+    // Yaw and pitch turned by, already scaled by sensitivity
+    pub look: Vec2,
+    // Left stick, with the deadzone already applied
+    pub stick: Vec2,
 }
 
 // Data
-#[derive(Hash, PartialEq, Eq, Clone, Actionlike)]
+#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug, Actionlike, Serialize, Deserialize)]
 pub enum Action {
     WalkForward,
     WalkBackward,
@@ -77,6 +308,8 @@ pub enum Action {
     StrafeRight,
     Jump,
     Crouch,
+    Sprint,
+    Mine,
     //LookUp,
     //LookDown,
     //LookLeft,
@@ -0,0 +1,62 @@
+use std::{env, time::Duration};
+
+use bevy::{prelude::*, app::ScheduleRunnerSettings, log::LogPlugin, transform::TransformPlugin};
+use iyes_loopless::{prelude::AppLooplessStateExt, state::NextState};
+
+use zombies_gold::{GameState, SimulationPlugin, map::WorldSeed, save::SavedWorld, net::DEFAULT_PORT, server::{NetServer, NetServerPlugin}};
+
+
+// Consts
+const TICK_RATE: f64 = 60.0;
+
+fn main() {
+    let server = match NetServer::bind(DEFAULT_PORT) {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("Couldn't listen on port {}: {}", DEFAULT_PORT, error);
+            return;
+        }
+    };
+
+    App::new()
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / TICK_RATE)))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(LogPlugin)
+        .add_plugin(TransformPlugin)
+
+        .add_loopless_state(GameState::Loading)
+
+        .add_startup_system(choose_world)
+        .add_plugin(SimulationPlugin)
+        .insert_resource(server)
+        .add_plugin(NetServerPlugin)
+        .add_enter_system(GameState::SpawnActors, start_playing)
+
+        .run();
+}
+
+/// Stands in for the main menu. Loads the saved world if there is one, otherwise generates a new world from the seed given as the first argument.
+fn choose_world (
+    mut commands: Commands,
+) {
+    if let Some(saved) = SavedWorld::exists().then(SavedWorld::load).flatten() {
+        info!("Loading saved world with seed {}", saved.seed);
+        commands.insert_resource(WorldSeed(saved.seed));
+        commands.insert_resource(saved);
+    }
+    else {
+        let seed = env::args().nth(1).and_then(|seed| seed.parse::<u64>().ok()).unwrap_or(0);
+        info!("Generating world with seed {}", seed);
+        commands.insert_resource(WorldSeed(seed));
+    }
+
+    commands.insert_resource(NextState(GameState::StartMapGen));
+}
+
+/// Players are spawned by their own clients, so there's nothing to wait on once the map is ready.
+fn start_playing (
+    mut commands: Commands,
+) {
+    info!("World ready");
+    commands.insert_resource(NextState(GameState::Playing));
+}
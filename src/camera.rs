@@ -0,0 +1,128 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::{map::LoadedChunks, physics::{Velocity, StepTime}, keybinds::{Keybinds, BindInputs}};
+
+
+// Consts
+// Just shy of straight up/down, so yaw stays well defined.
+const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
+
+// How far the third person camera keeps from walls
+const CAMERA_RADIUS: f32 = 0.2;
+
+// Bobs per meter walked
+const BOB_FREQUENCY: f32 = 1.8;
+// Speed at which the bob is at full strength
+const BOB_FULL_SPEED: f32 = 8.0;
+
+// Systems
+pub fn toggle_camera_mode (
+    inputs: BindInputs,
+    keybinds: Res<Keybinds>,
+
+    mut query: Query<&mut CameraController>,
+) {
+    if !inputs.just_pressed(&keybinds.toggle_camera) {
+        return;
+    }
+
+    for mut controller in query.iter_mut() {
+        controller.mode = match controller.mode {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::FirstPerson,
+        };
+    }
+}
+
+/// Places cameras from their controller. Runs after physics so the camera follows where its parent ended up.
+pub fn update_cameras (
+    time: Res<StepTime>,
+    keybinds: Res<Keybinds>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    parent_query: Query<(&Transform, Option<&Velocity>), Without<CameraController>>,
+    mut camera_query: Query<(&Parent, &mut CameraController, &mut Transform, &mut PerspectiveProjection)>,
+) {
+    for (parent, mut controller, mut transform, mut projection) in camera_query.iter_mut() {
+        let (parent_transform, opt_velocity) = match parent_query.get(**parent) {
+            Ok(parent) => parent,
+            Err(_) => continue,
+        };
+
+        let fov = keybinds.fov_degrees.to_radians();
+        if projection.fov != fov {
+            projection.fov = fov;
+        }
+
+        let pitch = Quat::from_rotation_x(controller.pitch);
+        let eye = Vec3::new(0.0, controller.eye_height, 0.0);
+
+        transform.rotation = pitch;
+
+        match controller.mode {
+            CameraMode::FirstPerson => {
+                let speed = opt_velocity.map_or(0.0, |velocity| Vec2::new(velocity.x, velocity.z).length());
+                controller.bob_phase = (controller.bob_phase + speed * BOB_FREQUENCY * time.delta_seconds()) % std::f32::consts::TAU;
+
+                let bob_strength = (speed / BOB_FULL_SPEED).min(1.0) * keybinds.view_bob;
+                let bob = Vec3::new(0.0, controller.bob_phase.sin().abs() * bob_strength, 0.0);
+
+                transform.translation = eye + bob;
+            }
+            CameraMode::ThirdPerson => {
+                // Pull the camera in if there's a block between it and our head.
+                let world_eye = parent_transform.translation + parent_transform.rotation * eye;
+                let world_back = parent_transform.rotation * pitch * Vec3::Z;
+
+                let distance = match loaded_chunks.raycast(world_eye, world_back, controller.third_person_distance + CAMERA_RADIUS) {
+                    Some(hit) => (hit.distance - CAMERA_RADIUS).max(0.0),
+                    None => controller.third_person_distance,
+                };
+
+                transform.translation = eye + pitch * Vec3::new(0.0, 0.0, distance);
+            }
+        }
+    }
+}
+
+// Components
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CameraMode {
+    FirstPerson,
+    ThirdPerson,
+}
+
+/// Goes on a camera that's a child of whatever it's looking out of.
+/// The parent gets turned to face yaw, the camera itself only pitches.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CameraController {
+    // Radians
+    pub yaw: f32,
+    pub pitch: f32,
+
+    pub mode: CameraMode,
+    pub eye_height: f32,
+    pub third_person_distance: f32,
+
+    bob_phase: f32,
+}
+impl CameraController {
+    pub fn new (eye_height: f32) -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            mode: CameraMode::FirstPerson,
+            eye_height,
+            third_person_distance: 4.0,
+            bob_phase: 0.0,
+        }
+    }
+
+    /// Turns by the given amounts in radians, keeping pitch within PITCH_LIMIT.
+    pub fn turn (&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw) % std::f32::consts::TAU;
+        self.pitch = (self.pitch + pitch).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+}
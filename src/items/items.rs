@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::{map::BlockType, console::Console};
+
+
+// Consts
+pub const HOTBAR_SIZE: usize = 9;
+const MAX_STACK: u32 = 64;
+
+const HOTBAR_KEYS: [KeyCode; HOTBAR_SIZE] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3,
+                                             KeyCode::Key4, KeyCode::Key5, KeyCode::Key6,
+                                             KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+                                            ];
+
+// Systems
+pub fn select_hotbar (
+    keys: Res<Input<KeyCode>>,
+    console: Res<Console>,
+
+    mut query: Query<&mut Inventory>,
+) {
+    // Numbers typed into the console aren't hotbar picks
+    if console.open {
+        return;
+    }
+
+    for (slot, key) in HOTBAR_KEYS.iter().enumerate() {
+        if keys.just_pressed(*key) {
+            for mut inventory in query.iter_mut() {
+                inventory.selected = slot;
+            }
+        }
+    }
+}
+
+// Components
+#[derive(Component, Default, Clone, Debug)]
+pub struct Inventory {
+    pub slots: [Option<ItemStack>; HOTBAR_SIZE],
+    pub selected: usize,
+    pub gold: u32,
+}
+impl Inventory {
+    /// Adds a block, stacking it with what we've already got where possible.
+    /// Returns false if there was no room for it.
+    pub fn add_block (&mut self, block_type: BlockType) -> bool {
+        if block_type == BlockType::GoldOre {
+            self.gold += 1;
+            return true;
+        }
+
+        if let Some(stack) = self.slots.iter_mut().flatten().find(|stack| stack.block_type == block_type && stack.count < MAX_STACK) {
+            stack.count += 1;
+            return true;
+        }
+
+        if let Some(slot) = self.slots.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(ItemStack { block_type, count: 1 });
+            return true;
+        }
+
+        false
+    }
+
+    pub fn selected_stack (&self) -> Option<&ItemStack> {
+        self.slots[self.selected].as_ref()
+    }
+}
+
+// Data
+#[derive(Clone, Copy, Debug)]
+pub struct ItemStack {
+    pub block_type: BlockType,
+    pub count: u32,
+}
@@ -0,0 +1,252 @@
+use std::{fs, marker::PhantomData};
+
+use bevy::{prelude::*, ecs::system::SystemParam};
+use leafwing_input_manager::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::actions::Action;
+
+
+// Consts
+pub const KEYBINDS_PATH: &str = "keybinds.ron";
+
+// Plugin
+#[derive(Default)]
+pub struct KeybindsPlugin;
+impl Plugin for KeybindsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .insert_resource(Keybinds::load());
+
+    }
+}
+
+// Systems
+/// Rebuilds every InputMap whenever the keybinds change, and points them at the first connected gamepad.
+pub fn apply_keybinds (
+    keybinds: Res<Keybinds>,
+    gamepads: Res<Gamepads>,
+
+    mut query: Query<&mut InputMap<Action>>,
+) {
+    if !keybinds.is_changed() && !gamepads.is_changed() {
+        return;
+    }
+
+    for mut input_map in query.iter_mut() {
+        *input_map = keybinds.input_map();
+
+        if let Some(gamepad) = gamepads.iter().next() {
+            input_map.set_gamepad(*gamepad);
+        }
+    }
+}
+
+// Helper functions
+/// Scales a stick so it reads zero inside the deadzone and ramps smoothly up to full outside it.
+pub fn apply_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= deadzone || length == 0.0 {
+        return Vec2::ZERO;
+    }
+
+    stick * (((length - deadzone) / (1.0 - deadzone)).min(1.0) / length)
+}
+
+// Data
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bind {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+impl Bind {
+    pub fn is_gamepad(&self) -> bool {
+        matches!(self, Bind::Gamepad(_))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Bind::Key(key) => format!("{:?}", key),
+            Bind::Mouse(button) => format!("Mouse {:?}", button),
+            Bind::Gamepad(button) => format!("Pad {:?}", button),
+        }
+    }
+}
+
+/// Every binding, plus look settings. Lives in KEYBINDS_PATH.
+/// Anything missing from the file is filled in from the defaults.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Keybinds {
+    pub actions: Vec<(Action, Vec<Bind>)>,
+
+    pub lock_cursor: Vec<Bind>,
+    pub unlock_cursor: Vec<Bind>,
+    pub quit: Vec<Bind>,
+    pub rebind_menu: Vec<Bind>,
+    pub toggle_camera: Vec<Bind>,
+    pub toggle_spectator: Vec<Bind>,
+    pub console: Vec<Bind>,
+    // Corners of the world edit selection, set to the block being looked at
+    pub select_first: Vec<Bind>,
+    pub select_second: Vec<Bind>,
+    // Debug overlay toggles, see debug.rs
+    pub toggle_overlay: Vec<Bind>,
+    pub toggle_chunk_borders: Vec<Bind>,
+    pub toggle_aabbs: Vec<Bind>,
+    pub toggle_wireframe: Vec<Bind>,
+
+    // Radians per pixel of mouse motion
+    pub mouse_sensitivity: f32,
+    // Radians per second at full stick deflection
+    pub stick_sensitivity: f32,
+    pub stick_deadzone: f32,
+
+    pub fov_degrees: f32,
+    // How far the camera bobs while walking. 0.0 turns it off.
+    pub view_bob: f32,
+}
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            actions: vec![
+                (Action::WalkForward,  vec![Bind::Key(KeyCode::W), Bind::Gamepad(GamepadButtonType::DPadUp)]),
+                (Action::WalkBackward, vec![Bind::Key(KeyCode::S), Bind::Gamepad(GamepadButtonType::DPadDown)]),
+                (Action::StrafeLeft,   vec![Bind::Key(KeyCode::A), Bind::Gamepad(GamepadButtonType::DPadLeft)]),
+                (Action::StrafeRight,  vec![Bind::Key(KeyCode::D), Bind::Gamepad(GamepadButtonType::DPadRight)]),
+                (Action::Jump,         vec![Bind::Key(KeyCode::Space), Bind::Gamepad(GamepadButtonType::South)]),
+                (Action::Crouch,       vec![Bind::Key(KeyCode::LControl), Bind::Gamepad(GamepadButtonType::East)]),
+                (Action::Sprint,       vec![Bind::Key(KeyCode::LShift), Bind::Gamepad(GamepadButtonType::LeftThumb)]),
+                (Action::Mine,         vec![Bind::Mouse(MouseButton::Left), Bind::Gamepad(GamepadButtonType::RightTrigger2)]),
+            ],
+
+            lock_cursor: vec![Bind::Mouse(MouseButton::Left)],
+            unlock_cursor: vec![Bind::Key(KeyCode::Tab), Bind::Gamepad(GamepadButtonType::Select)],
+            quit: vec![Bind::Key(KeyCode::Escape)],
+            rebind_menu: vec![Bind::Key(KeyCode::F1)],
+            toggle_camera: vec![Bind::Key(KeyCode::F5), Bind::Gamepad(GamepadButtonType::RightThumb)],
+            toggle_spectator: vec![Bind::Key(KeyCode::F2)],
+            console: vec![Bind::Key(KeyCode::Grave)],
+            select_first: vec![Bind::Key(KeyCode::LBracket)],
+            select_second: vec![Bind::Key(KeyCode::RBracket)],
+            toggle_overlay: vec![Bind::Key(KeyCode::F3)],
+            toggle_chunk_borders: vec![Bind::Key(KeyCode::F4)],
+            toggle_aabbs: vec![Bind::Key(KeyCode::F6)],
+            toggle_wireframe: vec![Bind::Key(KeyCode::F7)],
+
+            mouse_sensitivity: 0.005,
+            stick_sensitivity: 3.0,
+            stick_deadzone: 0.15,
+
+            fov_degrees: 70.0,
+            view_bob: 0.05,
+        }
+    }
+}
+impl Keybinds {
+    /// Loads the keybinds file, writing out the defaults if there isn't one yet.
+    pub fn load() -> Self {
+        match fs::read_to_string(KEYBINDS_PATH) {
+            Ok(contents) => {
+                match ron::from_str::<Keybinds>(&contents) {
+                    Ok(mut keybinds) => {
+                        // Actions added since the file was written get their default binds.
+                        for (action, binds) in Keybinds::default().actions {
+                            if keybinds.binds(action).is_empty() {
+                                keybinds.actions.push((action, binds));
+                            }
+                        }
+                        keybinds
+                    }
+                    Err(error) => {
+                        warn!("Couldn't parse {}, using default keybinds: {}", KEYBINDS_PATH, error);
+                        Keybinds::default()
+                    }
+                }
+            }
+            Err(_) => {
+                let keybinds = Keybinds::default();
+                keybinds.save();
+                keybinds
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let contents = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(contents) => contents,
+            Err(error) => {
+                warn!("Couldn't serialize keybinds: {}", error);
+                return;
+            }
+        };
+
+        if let Err(error) = fs::write(KEYBINDS_PATH, contents) {
+            warn!("Couldn't write {}: {}", KEYBINDS_PATH, error);
+        }
+    }
+
+    pub fn input_map(&self) -> InputMap<Action> {
+        let mut input_map = InputMap::default();
+
+        for (action, binds) in self.actions.iter() {
+            for bind in binds {
+                match *bind {
+                    Bind::Key(key) => input_map.insert(*action, key),
+                    Bind::Mouse(button) => input_map.insert(*action, button),
+                    Bind::Gamepad(button) => input_map.insert(*action, button),
+                };
+            }
+        }
+
+        input_map
+    }
+
+    pub fn binds(&self, action: Action) -> &[Bind] {
+        self.actions.iter()
+            .find(|(bound_action, _)| *bound_action == action)
+            .map_or(&[], |(_, binds)| binds.as_slice())
+    }
+
+    /// Replaces the action's binding for the bind's device (keyboard and mouse count as one device), keeping the other.
+    pub fn rebind(&mut self, action: Action, bind: Bind) {
+        match self.actions.iter_mut().find(|(bound_action, _)| *bound_action == action) {
+            Some((_, binds)) => {
+                binds.retain(|existing| existing.is_gamepad() != bind.is_gamepad());
+                binds.push(bind);
+            }
+            None => {
+                self.actions.push((action, vec![bind]));
+            }
+        }
+    }
+}
+
+/// Everything needed to check a Bind.
+#[derive(SystemParam)]
+pub struct BindInputs<'w, 's> {
+    keys: Res<'w, Input<KeyCode>>,
+    mouse_buttons: Res<'w, Input<MouseButton>>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s usize>,
+}
+impl<'w, 's> BindInputs<'w, 's> {
+    pub fn just_pressed(&self, binds: &[Bind]) -> bool {
+        binds.iter().any(|bind| {
+            match *bind {
+                Bind::Key(key) => self.keys.just_pressed(key),
+                Bind::Mouse(button) => self.mouse_buttons.just_pressed(button),
+                Bind::Gamepad(button_type) => self.gamepad_buttons.get_just_pressed().any(|button| button.1 == button_type),
+            }
+        })
+    }
+
+    /// Returns whatever was pressed this frame, if anything. Used for rebinding.
+    pub fn first_just_pressed(&self) -> Option<Bind> {
+        self.keys.get_just_pressed().next().map(|key| Bind::Key(*key))
+            .or_else(|| self.mouse_buttons.get_just_pressed().next().map(|button| Bind::Mouse(*button)))
+            .or_else(|| self.gamepad_buttons.get_just_pressed().next().map(|button| Bind::Gamepad(button.1)))
+    }
+}
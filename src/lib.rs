@@ -0,0 +1,246 @@
+// Uhm, actually, its 3D ZOMBIES GOLD ????~
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet, FixedTimestepStage, IntoConditionalSystem};
+use leafwing_input_manager::plugin::InputManagerPlugin;
+
+
+#[path = "map/map.rs"]
+pub mod map;
+
+#[path = "map/coords.rs"]
+pub mod coords;
+
+#[path = "map/storage.rs"]
+pub mod storage;
+
+#[path = "map/lod.rs"]
+pub mod lod;
+
+#[path = "map/culling.rs"]
+pub mod culling;
+
+#[path = "map/save.rs"]
+pub mod save;
+
+#[path = "map/world_edit.rs"]
+pub mod world_edit;
+
+#[path = "map/fluids.rs"]
+pub mod fluids;
+
+#[path = "physics/physics.rs"]
+pub mod physics;
+
+#[path = "physics/falling_blocks.rs"]
+pub mod falling_blocks;
+
+#[path = "physics/structure.rs"]
+pub mod structure;
+
+pub mod actions;
+
+pub mod camera;
+
+pub mod keybinds;
+
+pub mod player;
+
+pub mod setup;
+
+pub mod spectator;
+
+pub mod replay;
+
+pub mod sky;
+
+#[path = "ui/ui.rs"]
+pub mod ui;
+
+#[path = "ui/rebind_menu.rs"]
+pub mod rebind_menu;
+
+#[path = "ui/menus.rs"]
+pub mod menus;
+
+#[path = "ui/hud.rs"]
+pub mod hud;
+
+#[path = "ui/console.rs"]
+pub mod console;
+
+#[path = "items/items.rs"]
+pub mod items;
+
+#[path = "net/net.rs"]
+pub mod net;
+
+#[path = "net/server.rs"]
+pub mod server;
+
+#[path = "net/client.rs"]
+pub mod client;
+
+#[cfg(feature = "debug")]
+#[path = "ui/debug.rs"]
+pub mod debug;
+
+
+// Plugins
+/// Everything that decides what happens in the world: the map, physics, fluids and items.
+/// Needs no renderer, so the server runs it on its own. Add GameState with add_loopless_state before this.
+/// When connected to a server, the parts that change the world are left to the server.
+#[derive(Default)]
+pub struct SimulationPlugin;
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .add_plugin(map::MapPlugin)
+         .add_plugin(fluids::FluidsPlugin)
+         .add_plugin(physics::PhysicsPlugin)
+         .add_plugin(falling_blocks::FallingBlocksPlugin)
+
+         .add_enter_system(GameState::StartMapGen, map::map_setup)
+         .add_system(map::check_map_gen.run_in_state(GameState::MapGen))
+
+         .add_system(map::set_block_chunk)
+
+         .add_system(map::lazy_mesher.after(map::set_block_chunk))
+
+         .add_system(fluids::activate_fluids.after(map::set_block_chunk))
+         .add_system(falling_blocks::detach_unsupported_blocks.run_if(net::has_authority).after(map::set_block_chunk))
+         .add_system(structure::collapse_unsupported.run_if(net::has_authority).after(map::set_block_chunk))
+         .add_stage_before(
+             CoreStage::Update,
+             "fluid_tick",
+             FixedTimestepStage::new(fluids::FLUID_TICK)
+                 .with_stage(SystemStage::parallel().with_system(fluids::fluid_tick.run_in_state(GameState::Playing).run_if(net::has_authority)))
+         )
+
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .label("physics")
+                 // Both write Velocity, so they need a fixed order for replays to come out the same.
+                 .with_system(physics::apply_gravity.before(physics::apply_velocity))
+                 .with_system(physics::apply_velocity)
+                 .with_system(player::breathe)
+                 .into()
+         )
+
+         .add_system(
+             falling_blocks::land_falling_blocks
+                 .run_in_state(GameState::Playing)
+                 .run_if(net::has_authority)
+                 .after("physics")
+         );
+
+    }
+}
+
+/// Everything to do with the person playing: rendering, input, cameras, menus and the HUD.
+#[derive(Default)]
+pub struct ClientPlugin;
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .add_plugin(InputManagerPlugin::<actions::Action>::default())
+
+         .add_plugin(keybinds::KeybindsPlugin)
+         .add_plugin(ui::GameUiPlugin)
+         .add_plugin(hud::HudPlugin)
+         .add_plugin(client::NetClientPlugin)
+         .add_plugin(replay::ReplayPlugin)
+         .add_plugin(sky::SkyPlugin)
+         .add_plugin(console::ConsolePlugin)
+         .add_plugin(world_edit::WorldEditPlugin)
+         .init_resource::<rebind_menu::RebindMenu>()
+         .init_resource::<actions::AnalogInput>()
+         .init_resource::<setup::ZombieAssets>()
+         .init_resource::<player::LookTarget>()
+
+         .add_startup_system(ui::spawn_ui_camera)
+
+         .add_system(menus::check_assets_loaded.run_in_state(GameState::Loading))
+
+         .add_enter_system(GameState::MainMenu, map::unload_map)
+         .add_enter_system(GameState::MainMenu, setup::despawn_actors)
+         .add_enter_system(GameState::MainMenu, fluids::clear_active_fluids)
+         .add_enter_system(GameState::MainMenu, menus::spawn_main_menu)
+         .add_enter_system(GameState::MainMenu, hud::despawn_hud)
+         .add_exit_system(GameState::MainMenu, menus::despawn_menus)
+         .add_system(menus::seed_input.run_in_state(GameState::MainMenu))
+
+         .add_enter_system(GameState::SpawnActors, setup::spawn_actors)
+         .add_enter_system(GameState::SpawnActors, hud::spawn_hud)
+
+         .add_enter_system(GameState::Paused, menus::spawn_pause_menu)
+         .add_exit_system(GameState::Paused, menus::despawn_menus)
+         .add_system(menus::pause_input.run_in_state(GameState::Paused))
+
+         .add_system(menus::menu_buttons)
+
+         .add_system(keybinds::apply_keybinds)
+         .add_system(rebind_menu::toggle_rebind_menu.before(rebind_menu::capture_rebind))
+         .add_system(rebind_menu::capture_rebind.before(rebind_menu::rebind_buttons))
+         .add_system(rebind_menu::rebind_buttons)
+         .add_system(rebind_menu::update_rebind_labels.after(rebind_menu::capture_rebind))
+
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .label("input")
+                 .before("physics")
+                 .with_system(actions::update_stance.before(actions::process_actions))
+                 .with_system(actions::process_actions)
+                 .with_system(actions::edge_protection.after(actions::process_actions))
+                 .with_system(spectator::fly.after(actions::process_actions))
+                 .with_system(spectator::toggle_spectator)
+                 .with_system(player::meta_input)
+                 .with_system(items::select_hotbar)
+                 .into()
+         )
+
+         .add_system(camera::toggle_camera_mode)
+         .add_system(lod::update_lod.before(map::lazy_mesher))
+         .add_system(culling::cull_chunks.after(map::lazy_mesher))
+         .add_system(
+             camera::update_cameras
+                 .run_in_state(GameState::Playing)
+                 .after("physics")
+         )
+
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .after(camera::update_cameras)
+                 .with_system(player::update_look_target)
+                 .into()
+         )
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .after(player::update_look_target)
+                 .with_system(player::mine)
+                 .with_system(hud::hud_visibility)
+                 .with_system(hud::update_block_outline.after(hud::hud_visibility))
+                 .with_system(hud::update_hud)
+                 .into()
+         );
+
+        #[cfg(feature = "debug")]
+        app.add_plugin(debug::DebugPlugin);
+
+    }
+}
+
+// Data
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum GameState {
+    Loading,
+    MainMenu,
+    Connecting,
+    StartMapGen, MapGen, SpawnActors,
+    Playing,
+    Paused,
+}
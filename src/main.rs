@@ -1,31 +1,16 @@
-// Uhm, actually, its 3D ZOMBIES GOLD ????~
+use std::{env, net::SocketAddr, path::PathBuf};
 
-use bevy::{
-    pbr::wireframe::WireframePlugin,
-    prelude::*,
-};
-use bevy_rapier3d::{plugin::{RapierPhysicsPlugin, NoUserData}, prelude::RapierDebugRenderPlugin};
-use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet};
-use leafwing_input_manager::plugin::InputManagerPlugin;
-use bevy_inspector_egui::{WorldInspectorPlugin, RegisterInspectable};
-use physics::Velocity;
+use bevy::prelude::*;
+use bevy_rapier3d::plugin::{RapierPhysicsPlugin, NoUserData};
+use iyes_loopless::prelude::AppLooplessStateExt;
 
-
-#[path = "map/map.rs"]
-pub mod map;
-
-#[path = "physics/physics.rs"]
-pub mod physics;
-
-pub mod actions;
-
-pub mod player;
-
-pub mod setup;
+use zombies_gold::{GameState, SimulationPlugin, ClientPlugin, client::ServerAddress, replay::{Recording, Playback}};
 
 
 fn main() {
-    App::new()
+    let mut app = App::new();
+
+    app
         .insert_resource(Msaa { samples: 4 })
         .insert_resource(WindowDescriptor{
             title: "3D ZOMBIES GOLD".to_string(),
@@ -35,55 +20,43 @@ fn main() {
 
         .add_plugins(DefaultPlugins)
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        //.add_plugin(RapierDebugRenderPlugin::default())
-        .add_plugin(InputManagerPlugin::<actions::Action>::default())
-        .add_plugin(WireframePlugin)
-        .add_plugin(WorldInspectorPlugin::new())
-        .register_inspectable::<Velocity>()
-
-        .add_plugin(map::MapPlugin)
-        .add_plugin(physics::PhysicsPlugin)
 
+        .add_loopless_state(GameState::Loading)
 
+        .add_plugin(SimulationPlugin)
+        .add_plugin(ClientPlugin);
 
-        //.add_loopless_state(GameState::Loading)
+    if let Some(address) = connect_address() {
+        app.insert_resource(ServerAddress(address));
+    }
 
-        .add_loopless_state(GameState::Playing)
+    if let Some(path) = arg_value("--record") {
+        app.insert_resource(Recording::new(PathBuf::from(path)));
+    }
 
-        .add_startup_system(setup::spawn_actors)
-        .add_startup_system(map::map_setup)
+    if let Some(path) = arg_value("--replay") {
+        if let Some(playback) = Playback::load(path.as_ref()) {
+            app.insert_resource(playback);
+        }
+    }
 
-        .add_system(map::set_block_chunk)
-
-        .add_system(map::lazy_mesher.after(map::set_block_chunk))
-
-        .add_system_set(
-            ConditionSet::new()
-                .run_in_state(GameState::Playing)
-                .label("input")
-                .before("physics")
-                .with_system(actions::process_actions)
-                .with_system(player::meta_input)
-                .into()
-        )
-
-        .add_system_set(
-            ConditionSet::new()
-                .run_in_state(GameState::Playing)
-                .label("physics")
-                .with_system(physics::apply_gravity)
-                .with_system(physics::apply_velocity)
-                .into()
-        )
+    app.run();
+}
 
-        
-        .run();
+/// Whatever follows `name` on the command line, if it's there.
+fn arg_value(name: &str) -> Option<String> {
+    env::args().skip_while(|arg| arg != name).nth(1)
 }
 
-// Data
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub enum GameState {
-    Loading,
-    StartMapGen, MapGen, SpawnActors,
-    Playing,
-}
\ No newline at end of file
+/// The address given with `--connect <address>`, if there is one.
+fn connect_address() -> Option<SocketAddr> {
+    let address = arg_value("--connect")?;
+
+    match address.parse() {
+        Ok(address) => Some(address),
+        Err(error) => {
+            eprintln!("Couldn't parse server address {}: {}", address, error);
+            None
+        }
+    }
+}
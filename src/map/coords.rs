@@ -0,0 +1,169 @@
+use bevy::{prelude::*, math::const_ivec3};
+use block_mesh::ndshape::{ConstShape, ConstShape3u32};
+
+use crate::map::BLOCK_SIDES;
+
+
+// Consts
+/// How many blocks along each side of a chunk. Everything else about chunk dimensions comes from this.
+pub const CHUNK_SIZE: usize = 16;
+pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+pub const CHUNK_EXTENT: IVec3 = const_ivec3!([CHUNK_SIZE as i32; 3]);
+
+// Shapes
+/// A flat chunk, indexed by `LocalPos`.
+pub type ChunkShape = ConstShape3u32<{ CHUNK_SIZE as u32 }, { CHUNK_SIZE as u32 }, { CHUNK_SIZE as u32 }>;
+/// A chunk plus a one block border of its neighbours, which is what the mesher works on.
+pub type SampleShape = ConstShape3u32<{ CHUNK_SIZE as u32 + 2 }, { CHUNK_SIZE as u32 + 2 }, { CHUNK_SIZE as u32 + 2 }>;
+
+// Data
+/// A block's position in the world.
+#[derive(Deref, DerefMut, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WorldPos(pub IVec3);
+impl WorldPos {
+    /// The chunk this block is in, and where it is inside it. Rounds towards negative infinity, so -1 is the last block of chunk -1.
+    pub fn split(self) -> (ChunkPos, LocalPos) {
+        let chunk = IVec3::new(
+            self.x.div_euclid(CHUNK_EXTENT.x),
+            self.y.div_euclid(CHUNK_EXTENT.y),
+            self.z.div_euclid(CHUNK_EXTENT.z),
+        );
+        let local = IVec3::new(
+            self.x.rem_euclid(CHUNK_EXTENT.x),
+            self.y.rem_euclid(CHUNK_EXTENT.y),
+            self.z.rem_euclid(CHUNK_EXTENT.z),
+        );
+
+        (ChunkPos(chunk), LocalPos(local.as_uvec3()))
+    }
+
+    pub fn chunk(self) -> ChunkPos {
+        self.split().0
+    }
+
+    pub fn local(self) -> LocalPos {
+        self.split().1
+    }
+
+    pub fn from_parts(chunk: ChunkPos, local: LocalPos) -> Self {
+        Self(chunk.origin().0 + local.as_ivec3())
+    }
+}
+
+/// A chunk's position in chunks, so chunk 1 starts at block CHUNK_SIZE.
+#[derive(Deref, DerefMut, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ChunkPos(pub IVec3);
+impl ChunkPos {
+    /// The world position of the chunk's first block.
+    pub fn origin(self) -> WorldPos {
+        WorldPos(self.0 * CHUNK_EXTENT)
+    }
+}
+
+/// A block's position inside its chunk. Every axis is below CHUNK_SIZE.
+#[derive(Deref, DerefMut, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LocalPos(pub UVec3);
+impl LocalPos {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self(UVec3::new(x, y, z))
+    }
+
+    /// The position's index into a flat chunk, with x varying fastest.
+    /// Goes through ChunkShape, so flat chunks can be copied straight into the mesher.
+    pub fn linearize(self) -> usize {
+        ChunkShape::linearize(self.to_array()) as usize
+    }
+
+    pub fn delinearize(index: usize) -> Self {
+        Self(UVec3::from(ChunkShape::delinearize(index as u32)))
+    }
+
+    /// The offsets to the neighbouring chunks this block touches, if it's on the edge of its chunk. In BLOCK_SIDES order.
+    pub fn touching_sides(self) -> impl Iterator<Item = IVec3> {
+        let local = self.0;
+
+        (0..3).flat_map(move |axis| {
+            let low = (local[axis] == 0).then(|| BLOCK_SIDES[axis * 2]);
+            let high = (local[axis] as usize == CHUNK_SIZE - 1).then(|| BLOCK_SIDES[axis * 2 + 1]);
+            low.into_iter().chain(high)
+        })
+    }
+}
+impl From<LocalPos> for [usize; 3] {
+    fn from(local: LocalPos) -> Self {
+        [local.x as usize, local.y as usize, local.z as usize]
+    }
+}
+impl From<[usize; 3]> for LocalPos {
+    fn from([x, y, z]: [usize; 3]) -> Self {
+        Self::new(x as u32, y as u32, z as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // Far enough out to cover plenty of chunks either side of zero without overflowing.
+    const RANGE: std::ops::Range<i32> = -1_000_000..1_000_000;
+
+    proptest! {
+        #[test]
+        fn split_round_trips(x in RANGE, y in RANGE, z in RANGE) {
+            let world = WorldPos(IVec3::new(x, y, z));
+            let (chunk, local) = world.split();
+
+            prop_assert_eq!(WorldPos::from_parts(chunk, local), world);
+        }
+
+        #[test]
+        fn local_is_inside_chunk(x in RANGE, y in RANGE, z in RANGE) {
+            let local = WorldPos(IVec3::new(x, y, z)).local();
+
+            prop_assert!(local.as_ivec3().cmplt(CHUNK_EXTENT).all());
+        }
+
+        #[test]
+        fn chunk_contains_block(x in RANGE, y in RANGE, z in RANGE) {
+            let world = WorldPos(IVec3::new(x, y, z));
+            let origin = world.chunk().origin();
+
+            prop_assert!(origin.cmple(*world).all());
+            prop_assert!((*world).cmplt(origin.0 + CHUNK_EXTENT).all());
+        }
+
+        #[test]
+        fn linearize_round_trips(x in 0..CHUNK_SIZE, y in 0..CHUNK_SIZE, z in 0..CHUNK_SIZE) {
+            let local = LocalPos::from([x, y, z]);
+
+            prop_assert!(local.linearize() < CHUNK_VOLUME);
+            prop_assert_eq!(LocalPos::delinearize(local.linearize()), local);
+        }
+
+        #[test]
+        fn touching_sides_lead_to_neighbours(x in RANGE, y in RANGE, z in RANGE) {
+            let world = WorldPos(IVec3::new(x, y, z));
+            let (chunk, local) = world.split();
+            let touching: Vec<IVec3> = local.touching_sides().collect();
+
+            for side in BLOCK_SIDES {
+                let neighbour = WorldPos(*world + side).chunk();
+                prop_assert_eq!(neighbour != chunk, touching.contains(&side));
+                if neighbour != chunk {
+                    prop_assert_eq!(*neighbour, *chunk + side);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn negative_chunk_edges() {
+        let size = CHUNK_SIZE as i32;
+
+        assert_eq!(WorldPos(IVec3::new(-1, 0, 0)).split(), (ChunkPos(IVec3::new(-1, 0, 0)), LocalPos::new(size as u32 - 1, 0, 0)));
+        assert_eq!(WorldPos(IVec3::new(-size, 0, 0)).split(), (ChunkPos(IVec3::new(-1, 0, 0)), LocalPos::new(0, 0, 0)));
+        assert_eq!(WorldPos(IVec3::new(-size - 1, 0, 0)).split(), (ChunkPos(IVec3::new(-2, 0, 0)), LocalPos::new(size as u32 - 1, 0, 0)));
+        assert_eq!(WorldPos(IVec3::new(size, 0, 0)).split(), (ChunkPos(IVec3::new(1, 0, 0)), LocalPos::new(0, 0, 0)));
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashSet};
+use block_mesh::VoxelVisibility;
+
+use crate::{map::{LoadedChunks, BLOCK_SIDES}, storage::ChunkStorage, coords::{WorldPos, LocalPos, CHUNK_SIZE, CHUNK_VOLUME}, camera::CameraController};
+
+
+// Systems
+/// Hides chunks the camera can't see into through open space.
+///
+/// Walks out from the camera's chunk, only moving from one chunk to the next through faces that are connected by empty space inside it,
+/// and never doubling back towards the camera. Anything it doesn't reach must be sealed off, like caves underground.
+/// Frustum culling is left to bevy, using the bounds chunks get when they're spawned.
+pub fn cull_chunks (
+    chunks: Res<LoadedChunks>,
+
+    camera_query: Query<&GlobalTransform, With<CameraController>>,
+    mut visibility_query: Query<&mut Visibility>,
+) {
+    let camera = match camera_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    let start = *WorldPos(camera.round().as_ivec3()).chunk();
+
+    // From outside the world there's nothing to walk through, so just draw everything.
+    let reached = if chunks.contains_key(&start) {
+        reachable_chunks(&chunks, start)
+    }
+    else {
+        chunks.keys().copied().collect()
+    };
+
+    for (index, chunk) in chunks.iter() {
+        let visible = reached.contains(index);
+
+        for entity in chunk.entities() {
+            if let Ok(mut visibility) = visibility_query.get_mut(entity) {
+                if visibility.is_visible != visible {
+                    visibility.is_visible = visible;
+                }
+            }
+        }
+    }
+}
+
+// Helper functions
+fn reachable_chunks(chunks: &LoadedChunks, start: IVec3) -> HashSet<IVec3> {
+    let mut reached = HashSet::default();
+    reached.insert(start);
+
+    // The chunk, the face we came in through, and every direction we've gone so far
+    let mut queue = VecDeque::new();
+    queue.push_back((start, None, 0u8));
+
+    while let Some((index, entered, directions)) = queue.pop_front() {
+        let connections = chunks[&index].connections;
+
+        for (side, offset) in BLOCK_SIDES.iter().enumerate() {
+            // Going back the way we came can't show anything we couldn't already see
+            if directions & (1 << opposite(side)) != 0 {
+                continue;
+            }
+            if let Some(entered) = entered {
+                if !connections.connects(entered, side) {
+                    continue;
+                }
+            }
+
+            let neighbour = index + *offset;
+            if !chunks.contains_key(&neighbour) || !reached.insert(neighbour) {
+                continue;
+            }
+
+            queue.push_back((neighbour, Some(opposite(side)), directions | (1 << side)));
+        }
+    }
+
+    reached
+}
+
+/// Bitmask of the chunk faces a block is on, in BLOCK_SIDES order.
+fn touching_faces(local: LocalPos) -> u8 {
+    let mut faces = 0;
+    for axis in 0..3 {
+        if local[axis] == 0 {
+            faces |= 1 << (axis * 2);
+        }
+        if local[axis] as usize == CHUNK_SIZE - 1 {
+            faces |= 1 << (axis * 2 + 1);
+        }
+    }
+    faces
+}
+
+/// The face across the chunk from this one, in BLOCK_SIDES order.
+fn opposite(side: usize) -> usize {
+    side ^ 1
+}
+
+// Data
+/// Which of a chunk's faces can see each other through the empty space inside it. Faces are in BLOCK_SIDES order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FaceConnections(u64);
+impl FaceConnections {
+    pub fn all() -> Self {
+        Self(u64::MAX)
+    }
+
+    pub fn none() -> Self {
+        Self(0)
+    }
+
+    pub fn connects(&self, from: usize, to: usize) -> bool {
+        self.0 & (1 << (from * 6 + to)) != 0
+    }
+
+    fn connect(&mut self, from: usize, to: usize) {
+        self.0 |= 1 << (from * 6 + to);
+        self.0 |= 1 << (to * 6 + from);
+    }
+
+    /// Flood fills every pocket of see through blocks, and connects every pair of faces each pocket touches.
+    pub fn compute(blocks: &ChunkStorage) -> Self {
+        if let Some(block) = blocks.uniform() {
+            return if block.block_type().visibility() == VoxelVisibility::Opaque {Self::none()} else {Self::all()};
+        }
+
+        let blocks = blocks.to_linear();
+        let mut visited = vec![false; CHUNK_VOLUME];
+        let mut connections = Self::none();
+        let mut stack = Vec::new();
+
+        for start in 0..CHUNK_VOLUME {
+            if visited[start] || blocks[start].block_type().visibility() == VoxelVisibility::Opaque {
+                continue;
+            }
+
+            // Bitmask of the faces this pocket touches
+            let mut faces = 0u8;
+            visited[start] = true;
+            stack.push(start);
+
+            while let Some(position) = stack.pop() {
+                let local = LocalPos::delinearize(position);
+                faces |= touching_faces(local);
+
+                for side in BLOCK_SIDES {
+                    let next = local.as_ivec3() + side;
+                    if next.cmplt(IVec3::ZERO).any() || next.cmpge(IVec3::splat(CHUNK_SIZE as i32)).any() {
+                        continue;
+                    }
+
+                    let next = LocalPos(next.as_uvec3()).linearize();
+                    if !visited[next] && blocks[next].block_type().visibility() != VoxelVisibility::Opaque {
+                        visited[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+
+            for from in 0..6 {
+                for to in 0..6 {
+                    if faces & (1 << from) != 0 && faces & (1 << to) != 0 {
+                        connections.connect(from, to);
+                    }
+                }
+            }
+        }
+
+        connections
+    }
+}
+impl Default for FaceConnections {
+    fn default() -> Self {
+        Self::all()
+    }
+}
@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, math::const_ivec3, utils::{HashMap, HashSet}};
+
+use crate::{map::{LoadedChunks, SetBlockEvent, SetBlockShape, Block, BlockType, BLOCK_SIDES, FLUID_SOURCE}};
+
+
+// Consts
+pub const FLUID_TICK: Duration = Duration::from_millis(250);
+
+// Caps how many cells get simulated per tick, which in turn caps how many chunks need remeshing.
+// Anything over the cap just waits for the next tick.
+const MAX_CELLS_PER_TICK: usize = 512;
+
+// The level given to fluid falling straight down. Stronger than anything flowing sideways, weaker than a source.
+const FLUID_FALLING: u8 = FLUID_SOURCE - 1;
+
+const HORIZONTAL_SIDES: [IVec3; 4] = [const_ivec3!([-1, 0, 0]),
+                                      const_ivec3!([1, 0, 0 ]),
+                                      const_ivec3!([0, 0, -1]),
+                                      const_ivec3!([0, 0, 1 ]),
+                                     ];
+
+// Plugin
+#[derive(Default)]
+pub struct FluidsPlugin;
+impl Plugin for FluidsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .init_resource::<ActiveFluids>();
+
+    }
+}
+
+// Systems
+/// Marks every cell touched by a block edit, and its neighbours, as needing a fluid update.
+pub fn activate_fluids (
+    mut active: ResMut<ActiveFluids>,
+    chunks: Res<LoadedChunks>,
+
+    mut ev_set_block: EventReader<SetBlockEvent>,
+) {
+    for ev in ev_set_block.iter() {
+        match ev.shape {
+            SetBlockShape::Block(location) => {
+                active.insert(location);
+                for side in BLOCK_SIDES {
+                    active.insert(location + side);
+                }
+            }
+            SetBlockShape::Chunk(_) | SetBlockShape::Range(..) => {
+                // Whole chunks and ranges are uniform, so only their shell (and the shell of what's around them) can flow.
+                // Only fluid is worth waking up though, otherwise every chunk of a new map queues thousands of cells that do nothing.
+                let (min, max) = ev.shape.bounds();
+                let min = min - IVec3::ONE;
+                let max = max + IVec3::ONE;
+
+                for x in min.x..=max.x {
+                    for y in min.y..=max.y {
+                        for z in min.z..=max.z {
+                            let on_shell = x <= min.x + 1 || x >= max.x - 1 ||
+                                           y <= min.y + 1 || y >= max.y - 1 ||
+                                           z <= min.z + 1 || z >= max.z - 1;
+
+                            let location = IVec3::new(x, y, z);
+                            if on_shell && is_fluid(&chunks, location) {
+                                active.insert(location);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Steps the fluid simulation once over the active cells. Meant to be run on a fixed timestep.
+pub fn fluid_tick (
+    mut active: ResMut<ActiveFluids>,
+    chunks: Res<LoadedChunks>,
+
+    mut ev_set_block: EventWriter<SetBlockEvent>,
+) {
+    if active.is_empty() {
+        return;
+    }
+
+    let cells: Vec<IVec3> = active.iter().copied().take(MAX_CELLS_PER_TICK).collect();
+    for cell in cells.iter() {
+        active.remove(cell);
+    }
+
+    // Every cell reads the world as it was at the start of the tick, and all changes get sent out together at the end.
+    let mut changes = HashMap::<IVec3, Block>::default();
+
+    for cell in cells {
+        update_cell(&chunks, cell, &mut changes);
+    }
+
+    for (location, block) in changes {
+        ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Block(location), block });
+    }
+}
+
+pub fn clear_active_fluids (
+    mut active: ResMut<ActiveFluids>,
+) {
+    active.clear();
+}
+
+// Helper functions
+fn is_fluid(chunks: &LoadedChunks, location: IVec3) -> bool {
+    chunks.get_block(location).map_or(false, |block| block.block_type().is_fluid())
+}
+
+fn update_cell(chunks: &LoadedChunks, location: IVec3, changes: &mut HashMap<IVec3, Block>) {
+    let block = match chunks.get_block(location) {
+        Some(block) if block.block_type().is_fluid() => block,
+        _ => return,
+    };
+    let fluid = block.block_type();
+
+    // Fluids meeting each other
+    for side in BLOCK_SIDES {
+        if let Some(neighbor) = chunks.get_block(location + side) {
+            if let Some(result) = reaction(fluid, neighbor.block_type()) {
+                changes.insert(location, Block::new(result));
+                return;
+            }
+        }
+    }
+
+    // Flowing fluid recalculates its level from its neighbours, so it dries up when its source goes away.
+    let mut level = block.level();
+    if level < FLUID_SOURCE {
+        let mut sources = 0;
+        let mut highest = 0;
+
+        for side in HORIZONTAL_SIDES {
+            if let Some(neighbor) = chunks.get_block(location + side) {
+                if neighbor.block_type() == fluid {
+                    if neighbor.level() == FLUID_SOURCE {
+                        sources += 1;
+                    }
+                    highest = highest.max(neighbor.level());
+                }
+            }
+        }
+
+        let fed_from_above = chunks.get_block(location + IVec3::Y)
+            .map_or(false, |above| above.block_type() == fluid);
+
+        let supported = chunks.get_block(location - IVec3::Y)
+            .map_or(true, |below| below.collidable() || (below.block_type() == fluid && below.level() == FLUID_SOURCE));
+
+        let new_level = if fluid_renews(fluid) && sources >= 2 && supported {
+            FLUID_SOURCE
+        }
+        else if fed_from_above {
+            FLUID_FALLING
+        }
+        else {
+            highest.saturating_sub(fluid_decay(fluid))
+        };
+
+        if new_level == 0 {
+            changes.insert(location, Block::new(BlockType::Air));
+            return;
+        }
+        else if new_level != level {
+            changes.insert(location, Block::new_fluid(fluid, new_level));
+            level = new_level;
+        }
+    }
+
+    // Falling takes priority over spreading out
+    let below_location = location - IVec3::Y;
+    if let Some(below) = chunks.get_block(below_location) {
+        if below.block_type() == BlockType::Air || (below.block_type() == fluid && below.level() < FLUID_FALLING) {
+            flow_into(changes, below_location, Block::new_fluid(fluid, FLUID_FALLING));
+            return;
+        }
+        else if below.block_type() == fluid {
+            return;
+        }
+    }
+
+    let spread_level = level.saturating_sub(fluid_decay(fluid));
+    if spread_level == 0 {
+        return;
+    }
+
+    for side in HORIZONTAL_SIDES {
+        let side_location = location + side;
+        if let Some(neighbor) = chunks.get_block(side_location) {
+            if neighbor.block_type() == BlockType::Air || (neighbor.block_type() == fluid && neighbor.level() < spread_level) {
+                flow_into(changes, side_location, Block::new_fluid(fluid, spread_level));
+            }
+        }
+    }
+}
+
+/// Records fluid flowing into a cell, keeping whichever flow is strongest if several reach it in one tick.
+fn flow_into(changes: &mut HashMap<IVec3, Block>, location: IVec3, block: Block) {
+    match changes.get(&location) {
+        Some(existing) if !existing.block_type().is_fluid() || existing.level() >= block.level() => {}
+        _ => { changes.insert(location, block); }
+    }
+}
+
+/// What a fluid turns into when it touches another block type, if anything.
+fn reaction(fluid: BlockType, other: BlockType) -> Option<BlockType> {
+    match (fluid, other) {
+        (BlockType::Lava, BlockType::Water) => Some(BlockType::Stone),
+        _ => None,
+    }
+}
+
+/// How many levels a fluid loses per block it spreads.
+fn fluid_decay(fluid: BlockType) -> u8 {
+    match fluid {
+        BlockType::Lava => 2,
+        _ => 1,
+    }
+}
+
+/// Whether flowing fluid between two sources becomes a source itself.
+fn fluid_renews(fluid: BlockType) -> bool {
+    match fluid {
+        BlockType::Water => true,
+        _ => false,
+    }
+}
+
+// Resources
+/// Cells that might change on the next fluid tick.
+#[derive(Deref, DerefMut, Default)]
+pub struct ActiveFluids(HashSet<IVec3>);
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use block_mesh::VoxelVisibility;
+use enum_map::EnumMap;
+
+use crate::{map::{LoadedChunks, Block, BlockType, BLOCK_SIDES}, storage::ChunkStorage, coords::{ChunkPos, LocalPos, CHUNK_SIZE}, camera::CameraController};
+
+
+// Consts
+/// The coarsest level of detail. Level n shrinks chunks by 2^n, so 3 is 8x.
+pub const MAX_LOD: usize = 3;
+// How far from the camera, in blocks, each coarser level of detail starts
+const LOD_DISTANCES: [f32; MAX_LOD] = [64.0, 128.0, 192.0];
+// How far past a boundary the camera has to get before a chunk switches, so they don't flicker back and forth
+const LOD_HYSTERESIS: f32 = 8.0;
+
+// Systems
+/// Picks each chunk's level of detail from its distance to the camera, and queues it (and its neighbours, for the seams) for remeshing when that changes.
+pub fn update_lod (
+    mut chunks: ResMut<LoadedChunks>,
+
+    camera_query: Query<&GlobalTransform, With<CameraController>>,
+) {
+    let camera = match camera_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    let mut changed = Vec::new();
+
+    for (index, chunk) in chunks.iter() {
+        let center = ChunkPos(*index).origin().as_vec3() + Vec3::splat(CHUNK_SIZE as f32 * 0.5);
+        let distance = center.distance(camera);
+
+        let mut lod = lod_for_distance(distance);
+        // Only move once we're properly past the boundary
+        if lod > chunk.lod {
+            lod = lod_for_distance(distance - LOD_HYSTERESIS).max(chunk.lod);
+        }
+        else if lod < chunk.lod {
+            lod = lod_for_distance(distance + LOD_HYSTERESIS).min(chunk.lod);
+        }
+
+        if lod != chunk.lod {
+            changed.push((*index, lod));
+        }
+    }
+
+    for (index, lod) in changed {
+        if let Some(chunk) = chunks.get_mut(&index) {
+            chunk.lod = lod;
+            chunk.meshed = false;
+        }
+
+        for side in BLOCK_SIDES {
+            if let Some(neighbour) = chunks.get_mut(&(index + side)) {
+                neighbour.meshed = false;
+            }
+        }
+    }
+}
+
+// Helper functions
+fn lod_for_distance(distance: f32) -> usize {
+    LOD_DISTANCES.iter().filter(|start| distance >= **start).count()
+}
+
+/// A chunk's blocks shrunk by 2^lod along each side, in LocalPos order at the smaller size (x fastest).
+/// Each cell becomes whatever most of it is: the most common opaque block if at least half of it is opaque, otherwise the most common of the rest.
+pub fn downsample(blocks: &ChunkStorage, lod: usize) -> Vec<Block> {
+    if lod == 0 {
+        return blocks.to_linear();
+    }
+
+    let cells = CHUNK_SIZE >> lod;
+    if let Some(block) = blocks.uniform() {
+        return vec![block; cells * cells * cells];
+    }
+
+    let scale = 1 << lod;
+    let full = blocks.to_linear();
+    let mut downsampled = Vec::with_capacity(cells * cells * cells);
+
+    for z in 0..cells {
+        for y in 0..cells {
+            for x in 0..cells {
+                let mut counts = EnumMap::<BlockType, u32>::default();
+
+                for dz in 0..scale {
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let local = LocalPos::from([x * scale + dx, y * scale + dy, z * scale + dz]);
+                            counts[full[local.linearize()].block_type()] += 1;
+                        }
+                    }
+                }
+
+                let opaque = |block_type: BlockType| block_type.visibility() == VoxelVisibility::Opaque;
+                let opaque_count: u32 = counts.iter().filter(|(block_type, _)| opaque(*block_type)).map(|(_, count)| count).sum();
+                let want_opaque = opaque_count * 2 >= (scale * scale * scale) as u32;
+
+                let block_type = counts.iter()
+                    .filter(|(block_type, count)| **count > 0 && opaque(*block_type) == want_opaque)
+                    .max_by_key(|(_, count)| **count)
+                    .map_or(BlockType::Air, |(block_type, _)| block_type);
+
+                downsampled.push(Block::new(block_type));
+            }
+        }
+    }
+
+    downsampled
+}
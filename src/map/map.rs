@@ -1,24 +1,25 @@
 use std::ops::{Index, IndexMut};
 
 use bevy::math::{Vec3A, const_ivec3};
-use bevy::pbr::wireframe::WireframeConfig;
-use bevy::render::mesh::{PrimitiveTopology, VertexAttributeValues, Indices};
+use bevy::render::{mesh::{PrimitiveTopology, VertexAttributeValues, Indices}, primitives::Aabb};
 use bevy::{prelude::*, utils::HashMap};
 use enum_map::{EnumMap, Enum};
-use ndarray::{Array3, Shape, Dim, Array};
-use block_mesh::ndshape::{ConstShape, ConstShape3u32, ConstShape3usize};
+use ndarray::{Array3, Shape, Dim};
+use block_mesh::ndshape::ConstShape;
 use block_mesh::{greedy_quads, visible_block_faces, GreedyQuadsBuffer, MergeVoxel, UnitQuadBuffer, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG};
-use ndcopy::{copy3, fill3};
+use iyes_loopless::state::NextState;
+use serde::{Serialize, Deserialize};
 
-use crate::physics::AabbCollider;
+use crate::{physics::AabbCollider, save::SavedWorld, storage::ChunkStorage, coords::{WorldPos, ChunkPos, LocalPos, SampleShape, CHUNK_SIZE, CHUNK_EXTENT}, lod::downsample, culling::FaceConnections, GameState};
 
 
 // Consts
-const CHUNK_WIDTH: usize = 16;
-const CHUNK_HEIGHT: usize = 16;
-const CHUNK_LENGTH: usize = 16;
+pub const FLUID_SOURCE: u8 = 8;
 
-const BLOCK_SIDES: [IVec3; 6] = [const_ivec3!([-1, 0, 0]),
+// One in this many blocks of the dirt layer is gold
+const GOLD_RARITY: u64 = 100;
+
+pub const BLOCK_SIDES: [IVec3; 6] = [const_ivec3!([-1, 0, 0]),
                                  const_ivec3!([1, 0, 0 ]),
                                  const_ivec3!([0, -1, 0]),
                                  const_ivec3!([0, 1, 0 ]),
@@ -26,14 +27,6 @@ const BLOCK_SIDES: [IVec3; 6] = [const_ivec3!([-1, 0, 0]),
                                  const_ivec3!([0, 0, 1 ]),
                                 ];
 
-const CHUNK_SIDES: [usize; 6] = [0,
-                                 CHUNK_WIDTH,
-                                 0,
-                                 CHUNK_HEIGHT,
-                                 0,
-                                 CHUNK_LENGTH,
-                                ];
-
 // Plugin
 #[derive(Default)]
 pub struct MapPlugin;
@@ -41,7 +34,11 @@ impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
         app
          .add_event::<SetBlockEvent>()
-         .init_resource::<LoadedChunks>();
+         .add_event::<LoadChunkEvent>()
+         .init_resource::<LoadedChunks>()
+         .init_resource::<MapGenTarget>()
+         .init_resource::<WorldConfig>()
+         .init_resource::<ChunkAssets>();
 
     }
 }
@@ -50,81 +47,187 @@ impl Plugin for MapPlugin {
 // Events
 
 pub struct SetBlockEvent {
-    shape: SetBlockShape,
-    block: Block,
+    pub shape: SetBlockShape,
+    pub block: Block,
+}
+
+/// Puts a whole chunk's worth of blocks into the world at once, replacing the chunk if it's already loaded.
+pub struct LoadChunkEvent {
+    pub index: IVec3,
+    pub blocks: Array3<Block>,
 }
 
 // Systems
+/// Runs on entering GameState::StartMapGen. Loads the saved world if there is one, otherwise generates a new one.
 pub fn map_setup (
-    mut wireframe_config: ResMut<WireframeConfig>,
+    mut commands: Commands,
+    mut target: ResMut<MapGenTarget>,
+    seed: Res<WorldSeed>,
+    config: Res<WorldConfig>,
+    opt_saved: Option<Res<SavedWorld>>,
+
     mut ev_set_block: EventWriter<SetBlockEvent>,
+    mut ev_load_chunk: EventWriter<LoadChunkEvent>,
 ) {
-    wireframe_config.global = true;
-
-    //create a 64x16x64 grid of blocks
-    //save this to a chunks resource thingy.
-    let dirt = Block::new(BlockType::Dirt);
-    let air = Block::new(BlockType::Air);
-
-    for x in -2..2 {
-        for z in -2..2 {
-            for y in -2..2 {
-                if y == -2 {
-                    ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Chunk(IVec3::new(x, y, z)), block: dirt })
-                } else {
-                    ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Chunk(IVec3::new(x, y, z)), block: air })
+    target.clear();
+
+    if let Some(saved) = opt_saved {
+        for chunk in saved.chunks.iter() {
+            if let Some(blocks) = chunk.to_array() {
+                ev_load_chunk.send(LoadChunkEvent { index: chunk.index, blocks });
+                target.push(chunk.index);
+            }
+        }
+
+        commands.insert_resource(saved.config);
+        commands.insert_resource(SpawnPoint(saved.player_position));
+        commands.remove_resource::<SavedWorld>();
+    }
+    else {
+        // A 64 block wide world from min_height to max_height, with the lowest chunk filled with dirt
+        let dirt = Block::new(BlockType::Dirt);
+        let air = Block::new(BlockType::Air);
+        let infinium = Block::new(BlockType::Infinium);
+
+        let (min_chunk, max_chunk) = config.chunk_range();
+        // The first block above the dirt
+        let ground = (min_chunk + 1) * CHUNK_SIZE as i32;
+
+        for x in -2..2 {
+            for z in -2..2 {
+                for y in min_chunk..=max_chunk {
+                    let index = IVec3::new(x, y, z);
+                    let origin = ChunkPos(index).origin();
+
+                    if origin.y >= ground {
+                        ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Chunk(index), block: air });
+                    }
+                    else {
+                        let blocks = Array3::from_shape_fn((CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE), |(_, local_y, _)| {
+                            let y = origin.y + local_y as i32;
+                            if y < config.min_height || (config.bedrock && y == config.min_height) {
+                                infinium
+                            }
+                            else {
+                                dirt
+                            }
+                        });
+                        ev_load_chunk.send(LoadChunkEvent { index, blocks });
+                    }
+                    target.push(index);
                 }
             }
         }
+
+        // Sprinkle gold through the dirt. Events are handled after chunk loads, so these land after the chunks above are filled.
+        let gold = Block::new(BlockType::GoldOre);
+        let min = IVec3::new(-2 * CHUNK_SIZE as i32, config.min_height + 1, -2 * CHUNK_SIZE as i32);
+        let max = IVec3::new(2 * CHUNK_SIZE as i32 - 1, ground - 1, 2 * CHUNK_SIZE as i32 - 1);
+
+        for position in WithinBoxIterator::new(min, max) {
+            if hash_position(**seed, position) % GOLD_RARITY == 0 {
+                ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Block(position), block: gold });
+            }
+        }
+
+        commands.insert_resource(SpawnPoint(Vec3::ZERO));
+    }
+
+    commands.insert_resource(NextState(GameState::MapGen));
+}
+
+/// Runs during GameState::MapGen. Moves on to spawning actors once every chunk we asked for is loaded and meshed.
+pub fn check_map_gen (
+    mut commands: Commands,
+    chunks: Res<LoadedChunks>,
+    target: Res<MapGenTarget>,
+) {
+    let done = target.iter().all(|index| {
+        chunks.get(index).map_or(false, |chunk| chunk.meshed)
+    });
+
+    if done {
+        commands.insert_resource(NextState(GameState::SpawnActors));
+    }
+}
+
+/// Despawns every chunk and forgets about them, ready for another world to be loaded.
+pub fn unload_map (
+    mut commands: Commands,
+    mut chunks: ResMut<LoadedChunks>,
+) {
+    for (_, chunk) in chunks.drain() {
+        commands.entity(chunk.entity).despawn_recursive();
     }
 }
 
 pub fn set_block_chunk (
     mut chunks: ResMut<LoadedChunks>,
+    config: Res<WorldConfig>,
 
     mut ev_set_block_chunk: EventReader<SetBlockEvent>,
+    mut ev_load_chunk: EventReader<LoadChunkEvent>,
 
     mut commands: Commands,
-
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
+    for ev in ev_load_chunk.iter() {
+        chunks.load_chunk(ev.index, ChunkStorage::from_dense(&ev.blocks), &mut commands);
+    }
+
     for ev in ev_set_block_chunk.iter() {
         match ev.shape {
             SetBlockShape::Chunk(location) => {
                 chunks.set_block_chunk(location, ev.block, &mut commands);
             }
 
-            _ => {
-                // TODO: add the other match arms lol
-                todo!();
+            SetBlockShape::Block(location) => {
+                if config.is_editable(location) {
+                    chunks.set_block(location, ev.block);
+                }
             }
 
-        }
+            SetBlockShape::Range(..) => {
+                let (min, max) = ev.shape.bounds();
 
-
-        
+                for location in WithinBoxIterator::new(min, max) {
+                    if config.is_editable(location) {
+                        chunks.set_block(location, ev.block);
+                    }
+                }
+            }
+        }
     }
 }
 
 pub fn lazy_mesher (
-    chunks: Res<LoadedChunks>,
+    mut chunks: ResMut<LoadedChunks>,
 
     mut ev_set_block_chunk: EventReader<SetBlockEvent>,
+    mut ev_load_chunk: EventReader<LoadChunkEvent>,
 
     mut commands: Commands,
 
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    chunk_assets: Res<ChunkAssets>,
+    // Missing when there's no renderer, like on the server.
+    opt_meshes: Option<ResMut<Assets<Mesh>>>,
 ) {
-    if ev_set_block_chunk.is_empty() {
+    if ev_set_block_chunk.is_empty() && ev_load_chunk.is_empty() && chunks.values().all(|chunk| chunk.meshed) {
         return;
     }
 
-    let mut material = StandardMaterial::from(Color::rgb(0.0, 0.0, 0.0));
-    material.perceptual_roughness = 0.9;
+    // Anything marked as needing a mesh, like chunks that changed level of detail
+    let mut need_mesh: Vec<IVec3> = chunks.iter()
+        .filter(|(_, chunk)| !chunk.meshed)
+        .map(|(index, _)| *index)
+        .collect();
 
-    let mut need_mesh = Vec::<IVec3>::new();
+    for ev in ev_load_chunk.iter() {
+        add_no_dupe(&mut need_mesh, ev.index);
+
+        for offset in BLOCK_SIDES {
+            add_no_dupe(&mut need_mesh, ev.index + offset);
+        }
+    }
 
     for ev in ev_set_block_chunk.iter() {
         match ev.shape {
@@ -133,15 +236,9 @@ pub fn lazy_mesher (
 
                 add_no_dupe(&mut need_mesh, chunk_index);
 
-                for i in 0..=2 {
-                    if block_index[i] == CHUNK_SIDES[i] {
-                        let modified_index = chunk_index + BLOCK_SIDES[i];
-                        add_no_dupe(&mut need_mesh, modified_index);
-                    }
-                    else if block_index[i] == CHUNK_SIDES[i+1] {
-                        let modified_index = chunk_index + BLOCK_SIDES[i+1];
-                        add_no_dupe(&mut need_mesh, modified_index);
-                    }
+                // Blocks on the edge of a chunk show up in its neighbours' meshes too
+                for side in block_index.touching_sides() {
+                    add_no_dupe(&mut need_mesh, chunk_index + side);
                 }
             }
             SetBlockShape::Chunk(chunk_index) => {
@@ -152,110 +249,254 @@ pub fn lazy_mesher (
                     add_no_dupe(&mut need_mesh, modified_index);
                 }
             },
-            
-            _ => {
-                todo!();
+            SetBlockShape::Range(..) => {
+                // Grown by a block so chunks that only share a face with the range get remeshed too
+                let (min, max) = ev.shape.bounds();
+                let min = *WorldPos(min - IVec3::ONE).chunk();
+                let max = *WorldPos(max + IVec3::ONE).chunk();
+
+                for chunk_index in WithinBoxIterator::new(min, max) {
+                    add_no_dupe(&mut need_mesh, chunk_index);
+                }
             }
         }
     }
 
-    for location in need_mesh {
-        if let Some(chunk) = chunks.get(&location) {
-            let mesh = generate_greedy_mesh (&mut meshes, &chunks, location);
+    let mut meshes = match opt_meshes {
+        Some(meshes) => meshes,
+        None => {
+            // Nothing to draw, but map gen still waits on chunks being meshed.
+            for location in need_mesh {
+                if let Some(chunk) = chunks.get_mut(&location) {
+                    chunk.meshed = true;
+                }
+            }
+            return;
+        }
+    };
 
-            commands.entity(chunk.entity)
-                .insert(mesh)
-                .insert(materials.add(material.clone()));
+    for location in need_mesh {
+        let (mesh, translucent_mesh) = match chunks.get(&location) {
+            Some(_) if is_hidden(&chunks, location) => (None, None),
+            Some(_) => generate_greedy_mesh(&chunks, location),
+            None => continue,
+        };
+
+        if let Some(chunk) = chunks.get_mut(&location) {
+            let [entity, translucent_entity] = chunk.entities();
+
+            chunk.mesh = update_mesh(&mut commands, &mut meshes, entity, chunk.mesh.take(), mesh, &chunk_assets.material);
+            // Translucent faces live on a child entity sitting at the chunk's center,
+            // so bevy's transparent pass sorts them back-to-front per chunk.
+            chunk.translucent_mesh = update_mesh(&mut commands, &mut meshes, translucent_entity, chunk.translucent_mesh.take(), translucent_mesh, &chunk_assets.translucent_material);
+
+            chunk.meshed = true;
+            chunk.connections = FaceConnections::compute(&chunk.blocks);
         }
     }
 }
 
 // Helper functions
+/// A well mixed hash of a block position, for deterministic worldgen decisions.
+pub fn hash_position(seed: u64, position: IVec3) -> u64 {
+    // splitmix64 over the seed and each coordinate
+    let mut hash = seed;
+    for coordinate in position.to_array() {
+        hash = hash.wrapping_add(coordinate as u32 as u64).wrapping_add(0x9E3779B97F4A7C15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D049BB133111EB);
+        hash ^= hash >> 31;
+    }
+    hash
+}
+
 fn add_no_dupe<T: PartialEq>(vec: &mut Vec<T>, val: T) {
     if !vec.contains(&val) {vec.push(val)};
 }
 
-// Yoinked from block-mesh examples with modifications cause I can't be assed.
-fn generate_greedy_mesh(
+/// Whether a chunk can't have any faces: it's all air, or it's all opaque and so are all its neighbours.
+/// Uniform neighbours are all we check, it's not worth scanning their faces.
+fn is_hidden(chunks: &LoadedChunks, index: IVec3) -> bool {
+    let opaque = |block: Block| block.block_type.visibility() == VoxelVisibility::Opaque;
+
+    match chunks[&index].blocks.uniform() {
+        Some(block) if block.block_type.visibility() == VoxelVisibility::Empty => true,
+        Some(block) if opaque(block) => {
+            // Missing neighbours get meshed as Infinium, so they count as solid too.
+            BLOCK_SIDES.iter().all(|side| {
+                chunks.get(&(index + *side)).map_or(true, |neighbour| neighbour.blocks.uniform().map_or(false, opaque))
+            })
+        }
+        _ => false,
+    }
+}
+
+/// Puts a chunk mesh on an entity, writing over its old mesh asset if it had one instead of making another.
+/// Returns the handle the entity ends up with, if any.
+fn update_mesh(
+    commands: &mut Commands,
     meshes: &mut Assets<Mesh>,
+    entity: Entity,
+    old: Option<Handle<Mesh>>,
+    new: Option<Mesh>,
+    material: &Handle<StandardMaterial>,
+) -> Option<Handle<Mesh>> {
+    match (old, new) {
+        (Some(handle), Some(mesh)) => {
+            meshes.set_untracked(&handle, mesh);
+            Some(handle)
+        }
+        (None, Some(mesh)) => {
+            let handle = meshes.add(mesh);
+            commands.entity(entity)
+                .insert(handle.clone())
+                .insert(material.clone());
+            Some(handle)
+        }
+        // Dropping the last handle frees the old mesh.
+        (_, None) => {
+            commands.entity(entity)
+                .remove::<Handle<Mesh>>()
+                .remove::<Handle<StandardMaterial>>();
+            None
+        }
+    }
+}
+
+// Yoinked from block-mesh examples with modifications cause I can't be assed.
+/// Returns the opaque mesh of a chunk and the mesh of its translucent blocks, or None for either if it'd be empty.
+/// The translucent mesh is centered on the chunk, see `Chunk::translucent_entity`.
+pub fn generate_greedy_mesh(
     chunks: &LoadedChunks,
     index: IVec3,
-) -> Handle<Mesh> {
+) -> (Option<Mesh>, Option<Mesh>) {
+
+    let lod = chunks[&index].lod;
+    let cells = (CHUNK_SIZE >> lod) as i32;
+    let scale = (1 << lod) as f32;
+    let cell_index = |cell: IVec3| (cell.x + cells * (cell.y + cells * cell.z)) as usize;
+
+    // The chunk and the faces of its neighbours at this chunk's level of detail
+    let own = downsample(&chunks[&index].blocks, lod);
+    let borders: Vec<Border> = BLOCK_SIDES.iter().map(|side| {
+        match chunks.get(&(index + *side)) {
+            Some(chunk) if chunk.lod == lod => Border::Blocks(downsample(&chunk.blocks, lod)),
+            // Where levels of detail meet, both sides draw their edge faces so there's never a gap between them.
+            Some(_) => Border::Fill(Block::new(BlockType::Air)),
+            None => Border::Fill(Block::new(BlockType::Infinium)),
+        }
+    }).collect();
 
-    type SampleShape = ConstShape3u32<{ CHUNK_WIDTH as u32 + 2 }, { CHUNK_HEIGHT as u32 + 2 }, { CHUNK_LENGTH as u32 + 2 }>;
-    type ChunkShape = ConstShape3u32<{CHUNK_WIDTH as u32}, {CHUNK_HEIGHT as u32}, {CHUNK_LENGTH as u32}>;
+    // Cells go in the corner of the samples, with a one cell border. Anything past the border stays air.
+    let mut samples = vec![Block::new(BlockType::Air); SampleShape::USIZE];
 
-    let mut samples = Array::from_elem((CHUNK_WIDTH + 2, CHUNK_HEIGHT + 2, CHUNK_LENGTH + 2), Block::new(BlockType::Air));
-    let mut samples = samples.as_slice_mut().unwrap();
+    for sample in WithinBoxIterator::new(IVec3::ZERO, IVec3::splat(cells + 1)) {
+        let cell = sample - IVec3::ONE;
 
-    copy3(
-        [CHUNK_WIDTH as u32, CHUNK_HEIGHT as u32, CHUNK_LENGTH as u32],
-        chunks[&index].blocks.as_slice().unwrap(),
-        &ChunkShape {},
-        [0, 0, 0],
-        samples,
-        &SampleShape {},
-        [1, 1, 1],
-    );
+        let mut outside_axis = None;
+        let mut outside_count = 0;
+        for axis in 0..3 {
+            if cell[axis] < 0 || cell[axis] >= cells {
+                outside_axis = Some(axis);
+                outside_count += 1;
+            }
+        }
+
+        let block = match (outside_count, outside_axis) {
+            (0, _) => own[cell_index(cell)],
+            (1, Some(axis)) => {
+                let side = axis * 2 + (cell[axis] >= cells) as usize;
+                match &borders[side] {
+                    Border::Blocks(blocks) => blocks[cell_index(cell - BLOCK_SIDES[side] * cells)],
+                    Border::Fill(block) => *block,
+                }
+            }
+            // Edges and corners are never next to a face the mesher looks at
+            _ => continue,
+        };
 
-    let faces = [([1, CHUNK_HEIGHT as u32, CHUNK_LENGTH as u32], IVec3::new(-1, 0, 0), [CHUNK_WIDTH as u32 - 1, 0, 0] , [0, 1, 1])                          ,
-                 ([1, CHUNK_HEIGHT as u32, CHUNK_LENGTH as u32], IVec3::new(1, 0, 0) , [0, 0, 0]                      , [CHUNK_WIDTH as u32 - 1 + 2, 1, 1]) ,
-                 ([CHUNK_WIDTH as u32, 1, CHUNK_LENGTH as u32] , IVec3::new(0, -1, 0), [0, CHUNK_HEIGHT as u32 - 1, 0], [1, 0, 1])                          ,
-                 ([CHUNK_WIDTH as u32, 1, CHUNK_LENGTH as u32] , IVec3::new(0, 1, 0) , [0, 0, 0]                      , [1, CHUNK_HEIGHT as u32 - 1 + 2, 1]),
-                 ([CHUNK_WIDTH as u32, CHUNK_HEIGHT as u32, 1] , IVec3::new(0, 0, -1), [0, 0, CHUNK_LENGTH as u32 - 1], [1, 1, 0])                          ,
-                 ([CHUNK_WIDTH as u32, CHUNK_HEIGHT as u32, 1] , IVec3::new(0, 0, 1) , [0, 0, 0]                      , [1, 1, CHUNK_LENGTH as u32 - 1 + 2]),
-                ];
-
-    for (shape, offset, src_start, dst_start) in faces {
-        if let Some(chunk) = chunks.get(&(index + offset)) {
-            copy3(
-                shape,
-                chunk.blocks.as_slice().unwrap(),
-                &ChunkShape {},
-                src_start,
-                &mut samples,
-                &SampleShape {},
-                dst_start,
-            );
+        samples[SampleShape::linearize(sample.as_uvec3().to_array()) as usize] = block;
+    }
+
+    // Opaque pass. Translucent blocks are treated as empty so the opaque faces behind them still get drawn.
+    let opaque_samples: Vec<PassVoxel> = samples.iter().map(|block| {
+        if block.block_type.visibility() == VoxelVisibility::Opaque {
+            PassVoxel::new(VoxelVisibility::Opaque, block.block_type)
         }
         else {
+            PassVoxel::new(VoxelVisibility::Empty, block.block_type)
+        }
+    }).collect();
+
+    // Cells are scale blocks wide, but the first one still has to start where block 0 would.
+    let offset = Vec3::splat(1.0 - scale);
+    let opaque_mesh = mesh_pass(&opaque_samples, |_| true, cells as u32, scale, offset);
+
+    // Translucent passes, one per translucent type in the chunk.
+    // A type only gets faces against air and other translucent types, so faces between identical blocks are culled.
+    let mut translucent_types = Vec::<BlockType>::new();
+    for block in chunks[&index].blocks.palette() {
+        if block.block_type.visibility() == VoxelVisibility::Translucent {
+            add_no_dupe(&mut translucent_types, block.block_type);
+        }
+    }
 
-            let infinium = Block::new(BlockType::Infinium);
+    let center = Vec3::splat(CHUNK_SIZE as f32 * 0.5);
+    let mut translucent_mesh = MeshBuffers::default();
 
-            fill3(
-                shape,
-                infinium,
-                &mut samples,
-                &SampleShape {},
-                dst_start,
-            );
-        }
+    for translucent_type in translucent_types {
+        let pass_samples: Vec<PassVoxel> = samples.iter().map(|block| {
+            if block.block_type == translucent_type || block.block_type.visibility() == VoxelVisibility::Opaque {
+                PassVoxel::new(VoxelVisibility::Opaque, block.block_type)
+            }
+            else {
+                PassVoxel::new(VoxelVisibility::Empty, block.block_type)
+            }
+        }).collect();
+
+        // Opaque blocks are only in this pass to cull faces, so we throw away any quads that belong to them.
+        let pass_mesh = mesh_pass(&pass_samples, |voxel| voxel.merge == translucent_type, cells as u32, scale, offset - center);
+        translucent_mesh.append(pass_mesh);
     }
 
+    (opaque_mesh.into_non_empty_mesh(), translucent_mesh.into_non_empty_mesh())
+}
+
+/// Greedy meshes the first `cells` (plus border) of a sample array, keeping only quads whose voxel passes `keep`.
+/// Each cell is `scale` blocks wide.
+fn mesh_pass<F: Fn(&PassVoxel) -> bool>(
+    samples: &[PassVoxel],
+    keep: F,
+    cells: u32,
+    scale: f32,
+    offset: Vec3,
+) -> MeshBuffers {
     let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
 
-    let mut buffer = GreedyQuadsBuffer::new((CHUNK_WIDTH + 2) * (CHUNK_HEIGHT + 2) * (CHUNK_LENGTH + 2));
+    let mut buffer = GreedyQuadsBuffer::new(SampleShape::USIZE);
     greedy_quads(
         samples,
         &SampleShape {},
         [0; 3],
-        [CHUNK_WIDTH as u32 + 2 - 1, CHUNK_HEIGHT as u32 + 2 - 1, CHUNK_LENGTH as u32 + 2 - 1],
+        [cells + 1; 3],
         &faces,
         &mut buffer,
     );
 
-    let num_indices = buffer.quads.num_quads() * 6;
-    let num_vertices = buffer.quads.num_quads() * 4;
-    let mut indices = Vec::with_capacity(num_indices);
-    let mut positions = Vec::with_capacity(num_vertices);
-    let mut normals = Vec::with_capacity(num_vertices);
-    let mut tex_coords = Vec::with_capacity(num_vertices);
+    let mut mesh = MeshBuffers::with_capacity(buffer.quads.num_quads());
+
     for (group, face) in buffer.quads.groups.into_iter().zip(faces.into_iter()) {
         for quad in group.into_iter() {
-            indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
-            positions.extend_from_slice(&face.quad_mesh_positions(&quad, 1.0));
-            normals.extend_from_slice(&face.quad_mesh_normals());
-            tex_coords.extend_from_slice(&face.tex_coords(
+            if !keep(&samples[SampleShape::linearize(quad.minimum) as usize]) {
+                continue;
+            }
+
+            mesh.indices.extend_from_slice(&face.quad_mesh_indices(mesh.positions.len() as u32));
+            for position in face.quad_mesh_positions(&quad, scale) {
+                mesh.positions.push((Vec3::from(position) + offset).to_array());
+            }
+            mesh.normals.extend_from_slice(&face.quad_mesh_normals());
+            mesh.tex_coords.extend_from_slice(&face.tex_coords(
                 RIGHT_HANDED_Y_UP_CONFIG.u_flip_face,
                 true,
                 &quad,
@@ -263,13 +504,7 @@ fn generate_greedy_mesh(
         }
     }
 
-    let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
-    render_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, VertexAttributeValues::Float32x3(positions));
-    render_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, VertexAttributeValues::Float32x3(normals));
-    render_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, VertexAttributeValues::Float32x2(tex_coords));
-    render_mesh.set_indices(Some(Indices::U32(indices.clone())));
-
-    meshes.add(render_mesh)
+    mesh
 }
 
 // Yoinked as above too.
@@ -278,10 +513,100 @@ fn generate_greedy_mesh(
 //}
 
 // Data
+/// What a block looks like to a single meshing pass.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct PassVoxel {
+    visibility: VoxelVisibility,
+    merge: BlockType,
+}
+impl PassVoxel {
+    fn new (visibility: VoxelVisibility, merge: BlockType) -> Self {
+        Self {visibility, merge}
+    }
+}
+impl Voxel for PassVoxel {
+    fn get_visibility(&self) -> VoxelVisibility {
+        self.visibility
+    }
+}
+impl MergeVoxel for PassVoxel {
+    type MergeValue = BlockType;
+
+    fn merge_value(&self) -> Self::MergeValue {
+        self.merge
+    }
+}
+
+/// What goes in the border of a chunk's samples on one side.
+enum Border {
+    // The neighbour's blocks, at the same level of detail
+    Blocks(Vec<Block>),
+    Fill(Block),
+}
+
+#[derive(Default)]
+struct MeshBuffers {
+    indices: Vec<u32>,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    tex_coords: Vec<[f32; 2]>,
+}
+impl MeshBuffers {
+    fn with_capacity (num_quads: usize) -> Self {
+        Self {
+            indices: Vec::with_capacity(num_quads * 6),
+            positions: Vec::with_capacity(num_quads * 4),
+            normals: Vec::with_capacity(num_quads * 4),
+            tex_coords: Vec::with_capacity(num_quads * 4),
+        }
+    }
+
+    fn into_non_empty_mesh (self) -> Option<Mesh> {
+        if self.indices.is_empty() {
+            None
+        }
+        else {
+            Some(self.into_mesh())
+        }
+    }
+
+    fn append (&mut self, other: MeshBuffers) {
+        let base = self.positions.len() as u32;
+        self.indices.extend(other.indices.iter().map(|i| i + base));
+        self.positions.extend(other.positions);
+        self.normals.extend(other.normals);
+        self.tex_coords.extend(other.tex_coords);
+    }
+
+    fn into_mesh (self) -> Mesh {
+        let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, VertexAttributeValues::Float32x3(self.positions));
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, VertexAttributeValues::Float32x3(self.normals));
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, VertexAttributeValues::Float32x2(self.tex_coords));
+        render_mesh.set_indices(Some(Indices::U32(self.indices)));
+        render_mesh
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum SetBlockShape {
     Block(IVec3),
     Chunk(IVec3),
-    Range(IVec3),
+    // Every block between two corners, inclusive. The corners can be either way round.
+    Range(IVec3, IVec3),
+}
+impl SetBlockShape {
+    /// The lowest and highest corners of the blocks this covers, inclusive.
+    pub fn bounds(&self) -> (IVec3, IVec3) {
+        match *self {
+            SetBlockShape::Block(location) => (location, location),
+            SetBlockShape::Chunk(index) => {
+                let origin = *ChunkPos(index).origin();
+                (origin, origin + CHUNK_EXTENT - IVec3::ONE)
+            }
+            SetBlockShape::Range(a, b) => (a.min(b), a.max(b)),
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Clone)]
@@ -297,20 +622,43 @@ impl Default for BlockMaterial {
     }
 }
 
-#[derive(Default, Clone, Copy, Debug)]
+#[derive(Default, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Block {
     block_type: BlockType,
     damage: f32,
+    // How full a fluid block is. FLUID_SOURCE for sources, 0 for anything that isn't a fluid.
+    level: u8,
     // This will have the type of as well as the amount of material dropped per hit
     // It should be in the enum, not here.
     //material_drop:
 }
 impl Block {
     pub fn new (block_type: BlockType) -> Self {
-        Self {block_type, damage: 0.0}
+        let level = if block_type.is_fluid() {FLUID_SOURCE} else {0};
+        Self {block_type, damage: 0.0, level}
     }
     pub fn new_with_damage (block_type: BlockType, damage: f32) -> Self {
-        Self {block_type, damage}
+        Self {damage, ..Block::new(block_type)}
+    }
+    pub fn new_fluid (block_type: BlockType, level: u8) -> Self {
+        Self {block_type, damage: 0.0, level}
+    }
+
+    pub fn block_type (&self) -> BlockType {
+        self.block_type
+    }
+    pub fn level (&self) -> u8 {
+        self.level
+    }
+    pub fn damage (&self) -> f32 {
+        self.damage
+    }
+    pub fn set_damage (&mut self, damage: f32) {
+        self.damage = damage;
+    }
+    /// This block, as it was before anything hit it.
+    pub fn undamaged (&self) -> Self {
+        Self {damage: 0.0, ..*self}
     }
 
     pub fn collidable (&self) -> bool {
@@ -330,11 +678,19 @@ impl MergeVoxel for Block {
     }
 }
 
-#[derive(Enum, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(Enum, PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum BlockType {
     Infinium,
     Air,
     Dirt,
+    Glass,
+    Water,
+    Leaves,
+    Lava,
+    Stone,
+    Sand,
+    Gravel,
+    GoldOre,
 }
 impl Default for BlockType {
     fn default() -> Self {
@@ -347,6 +703,14 @@ impl BlockType {
             BlockType::Infinium => {VoxelVisibility::Opaque}
             BlockType::Air => {VoxelVisibility::Empty}
             BlockType::Dirt => {VoxelVisibility::Opaque}
+            BlockType::Glass => {VoxelVisibility::Translucent}
+            BlockType::Water => {VoxelVisibility::Translucent}
+            BlockType::Leaves => {VoxelVisibility::Translucent}
+            BlockType::Lava => {VoxelVisibility::Opaque}
+            BlockType::Stone => {VoxelVisibility::Opaque}
+            BlockType::Sand => {VoxelVisibility::Opaque}
+            BlockType::Gravel => {VoxelVisibility::Opaque}
+            BlockType::GoldOre => {VoxelVisibility::Opaque}
 
             _ => {VoxelVisibility::Opaque}
         }
@@ -356,19 +720,96 @@ impl BlockType {
             BlockType::Infinium => {true}
             BlockType::Air => {false}
             BlockType::Dirt => {true}
+            BlockType::Glass => {true}
+            BlockType::Water => {false}
+            BlockType::Leaves => {true}
+            BlockType::Lava => {false}
+            BlockType::Stone => {true}
+            BlockType::Sand => {true}
+            BlockType::Gravel => {true}
+            BlockType::GoldOre => {true}
 
             _ => {true}
         }
     }
+    /// Seconds of mining it takes to break this block.
+    pub fn hardness(&self) -> f32 {
+        match self {
+            BlockType::Infinium => {f32::INFINITY}
+            BlockType::Dirt => {0.5}
+            BlockType::Glass => {0.3}
+            BlockType::Leaves => {0.2}
+            BlockType::Stone => {1.5}
+            BlockType::Sand => {0.4}
+            BlockType::Gravel => {0.5}
+            BlockType::GoldOre => {2.0}
+
+            _ => {1.0}
+        }
+    }
+    /// How many blocks sideways (or down) from solid support this block can hold itself up.
+    pub fn max_span(&self) -> u8 {
+        match self {
+            BlockType::Infinium => {u8::MAX}
+            BlockType::Dirt => {2}
+            BlockType::Glass => {3}
+            BlockType::Leaves => {4}
+            BlockType::Stone => {6}
+            BlockType::Sand => {0}
+            BlockType::Gravel => {0}
+
+            _ => {4}
+        }
+    }
+    /// Whether this block holds up everything around it regardless of what's under it.
+    pub fn is_anchor(&self) -> bool {
+        matches!(self, BlockType::Infinium)
+    }
+    /// Whether this block turns into a falling block when there's nothing under it.
+    pub fn falls_when_unsupported(&self) -> bool {
+        matches!(self, BlockType::Sand | BlockType::Gravel)
+    }
+    pub fn is_fluid(&self) -> bool {
+        matches!(self, BlockType::Water | BlockType::Lava)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RaycastHit {
+    // The block that was hit
+    pub position: IVec3,
+    // The face of the block that was hit. Zero if the ray started inside the block.
+    pub normal: IVec3,
+    pub distance: f32,
 }
 
 pub struct Chunk {
-    blocks: Array3<Block>,
+    blocks: ChunkStorage,
     entity: Entity,
+    // Child of entity, centered in the chunk. Holds the translucent mesh.
+    translucent_entity: Entity,
+    // Whether the mesher has gotten to this chunk yet
+    pub meshed: bool,
+    // The level of detail it gets meshed at, see `lod::update_lod`
+    pub lod: usize,
+    // Which faces can see each other through it, updated when it's meshed. See `culling::cull_chunks`.
+    pub connections: FaceConnections,
+    // Kept so remeshing can write over the old assets
+    mesh: Option<Handle<Mesh>>,
+    translucent_mesh: Option<Handle<Mesh>>,
 }
 impl Chunk {
-    pub fn new(blocks: Array3<Block>, entity: Entity) -> Self {
-        Self {blocks, entity}
+    pub fn new(blocks: ChunkStorage, entity: Entity, translucent_entity: Entity) -> Self {
+        Self {blocks, entity, translucent_entity, meshed: false, lod: 0, connections: FaceConnections::all(), mesh: None, translucent_mesh: None}
+    }
+
+    pub fn blocks(&self) -> &ChunkStorage {
+        &self.blocks
+    }
+
+    /// The chunk's entity and its translucent child.
+    pub fn entities(&self) -> [Entity; 2] {
+        [self.entity, self.translucent_entity]
     }
 }
 
@@ -376,70 +817,178 @@ impl Chunk {
 #[derive(Deref, DerefMut, Default)]
 pub struct LoadedChunks(HashMap<IVec3, Chunk>);
 impl LoadedChunks {
-    pub fn index_block (index: IVec3) -> (IVec3, [usize; 3]) {
-        // block index is fine(?)
-        // chunk index needs to be 1 lower for negative indexes
-        let mut effective_index = index;
-
-        if index.x < 0 {
-            effective_index.x -= CHUNK_WIDTH as i32;
-        }
-        if index.y < 0 {
-            effective_index.y -= CHUNK_HEIGHT as i32;
-        }
-        if index.z < 0 {
-            effective_index.z -= CHUNK_LENGTH as i32;
-        }
-
-        (IVec3::new(effective_index.x / CHUNK_WIDTH as i32, effective_index.y / CHUNK_HEIGHT as i32, effective_index.z / CHUNK_LENGTH as i32),
-        [index.x as usize % CHUNK_WIDTH, index.y as usize % CHUNK_HEIGHT, index.z as usize % CHUNK_LENGTH])
+    /// Splits a world block position into the chunk it's in and its position inside that chunk. See `WorldPos::split`.
+    pub fn index_block (index: IVec3) -> (IVec3, LocalPos) {
+        let (chunk, local) = WorldPos(index).split();
+        (*chunk, local)
     }
 
-    pub fn get_block (&self, index: IVec3) -> Option<&Block> {
+    pub fn get_block (&self, index: IVec3) -> Option<Block> {
         let (chunk_index, block_index) = LoadedChunks::index_block(index);
 
-        if let Some(chunk) = &self.get(&chunk_index) {
-            Some(&chunk.blocks[block_index])
-        }
-        else {
-            None
-        }
+        self.get(&chunk_index).map(|chunk| chunk.blocks.get(block_index))
     }
 
-    pub fn get_block_mut (&mut self, index: IVec3) -> Option<&mut Block> {
+    /// Replaces a block. Returns false if its chunk isn't loaded.
+    pub fn set_block (&mut self, index: IVec3, block: Block) -> bool {
         let (chunk_index, block_index) = LoadedChunks::index_block(index);
 
         if let Some(chunk) = self.get_mut(&chunk_index) {
-            Some(&mut chunk.blocks[block_index])
+            chunk.blocks.set(block_index, block);
+            true
         }
         else {
-            None
+            false
         }
     }
 
-    
     pub fn set_block_chunk (&mut self, index: IVec3, block: Block, commands: &mut Commands) {
+        self.load_chunk(index, ChunkStorage::filled(block), commands);
+    }
+
+    pub fn load_chunk (&mut self, index: IVec3, blocks: ChunkStorage, commands: &mut Commands) {
         if let Some(chunk) = self.get_mut(&index) {
             // Change the blocks of the chunk
-            chunk.blocks = Array::from_elem((CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_LENGTH), block);
+            chunk.blocks = blocks;
         }
         else {
-            let blocks = Array::from_elem((CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_LENGTH), block);
-
             let chunk = commands
                 .spawn()
                 .insert(Transform {
-                    translation: IVec3::new(CHUNK_WIDTH as i32 * index.x, CHUNK_HEIGHT as i32 * index.y, CHUNK_LENGTH as i32 * index.z).as_vec3(),
+                    translation: ChunkPos(index).origin().as_vec3(),
+                    ..default()
+                })
+                .insert(GlobalTransform::identity())
+                // Bevy only works out bounds from the first mesh it sees, so give it ones that fit any mesh of the chunk.
+                // Meshes start a block in from the chunk's origin, see generate_greedy_mesh.
+                .insert(Aabb::from_min_max(Vec3::ONE, Vec3::splat(CHUNK_SIZE as f32 + 1.0)))
+                .insert(Visibility::default())
+                .insert(ComputedVisibility::default())
+                .id();
+
+            let translucent_chunk = commands
+                .spawn()
+                .insert(Transform {
+                    translation: Vec3::splat(CHUNK_SIZE as f32 * 0.5),
                     ..default()
                 })
                 .insert(GlobalTransform::identity())
+                .insert(Aabb::from_min_max(Vec3::splat(1.0 - CHUNK_SIZE as f32 * 0.5), Vec3::splat(1.0 + CHUNK_SIZE as f32 * 0.5)))
                 .insert(Visibility::default())
                 .insert(ComputedVisibility::default())
                 .id();
 
+            commands.entity(chunk).push_children(&[translucent_chunk]);
+
             // Add chunk to loaded chunks
-            self.entry(index).insert(Chunk::new(blocks, chunk));
+            self.entry(index).insert(Chunk::new(blocks, chunk, translucent_chunk));
+        }
+    }
+
+    /// Returns how much of the AABB is inside fluid from 0.0 to 1.0, and which fluid makes up most of that.
+    pub fn aabb_fluid_overlap(&self, aabb: AabbCollider) -> (f32, Option<BlockType>) {
+        let volume = aabb.get_extents().x * aabb.get_extents().y * aabb.get_extents().z;
+        if volume <= 0.0 {
+            return (0.0, None)
+        }
+
+        let min = aabb.min.round().as_ivec3();
+        let max = aabb.max.round().as_ivec3();
+
+        let mut overlap = 0.0;
+        let mut overlaps = EnumMap::<BlockType, f32>::default();
+
+        for position in WithinBoxIterator::new(min, max) {
+            if let Some(block) = self.get_block(position) {
+                if !block.block_type.is_fluid() {
+                    continue;
+                }
+
+                // Fluids that aren't sources don't fill their whole block.
+                let height = block.level as f32 / FLUID_SOURCE as f32;
+                let block_min = position.as_vec3() - Vec3::splat(0.5);
+                let block_max = block_min + Vec3::new(1.0, height, 1.0);
+
+                let extents = (aabb.max.min(block_max) - aabb.min.max(block_min)).max(Vec3::ZERO);
+                let block_overlap = extents.x * extents.y * extents.z;
+
+                overlap += block_overlap;
+                overlaps[block.block_type] += block_overlap;
+            }
+        }
+
+        if overlap <= 0.0 {
+            return (0.0, None)
         }
+
+        let fluid = overlaps.iter()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(block_type, _)| block_type);
+
+        ((overlap / volume).min(1.0), fluid)
+    }
+
+    /// Whether any collidable block overlaps the AABB. Touching doesn't count.
+    pub fn aabb_obstructed(&self, aabb: AabbCollider) -> bool {
+        // Shrink a little so an AABB resting flush against a wall or floor isn't counted as inside it.
+        let min = (aabb.min + Vec3::splat(0.01)).round().as_ivec3();
+        let max = (aabb.max - Vec3::splat(0.01)).round().as_ivec3();
+
+        WithinBoxIterator::new(min, max).any(|position| {
+            self.get_block(position).map_or(true, |block| block.collidable())
+        })
+    }
+
+    /// Whether there's anything collidable directly under the AABB's footprint.
+    pub fn aabb_grounded(&self, aabb: AabbCollider) -> bool {
+        let mut min = (aabb.min + Vec3::splat(0.01)).round().as_ivec3();
+        let mut max = (aabb.max - Vec3::splat(0.01)).round().as_ivec3();
+
+        let below = (aabb.min.y - 0.1).round() as i32;
+        min.y = below;
+        max.y = below;
+
+        WithinBoxIterator::new(min, max).any(|position| {
+            self.get_block(position).map_or(true, |block| block.collidable())
+        })
+    }
+
+    /// Steps through the blocks along a ray until it hits something collidable.
+    /// Unloaded chunks count as hits, same as for collision.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RaycastHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            return None
+        }
+
+        // Blocks are centered on whole numbers, so shift by half a block to get them to start on whole numbers.
+        let start = origin + Vec3::splat(0.5);
+        let mut position = start.floor().as_ivec3();
+
+        let step = direction.signum().as_ivec3();
+        let next_boundary = position.as_vec3() + direction.signum().max(Vec3::ZERO);
+        // How far along the ray we have to go to cross a boundary on each axis
+        let delta = (Vec3::ONE / direction).abs();
+        let mut t_max = Vec3::select(direction.cmpeq(Vec3::ZERO), Vec3::splat(f32::INFINITY), (next_boundary - start) / direction);
+
+        let mut distance = 0.0;
+        let mut normal = IVec3::ZERO;
+
+        while distance <= max_distance {
+            if self.get_block(position).map_or(true, |block| block.collidable()) {
+                return Some(RaycastHit { position, normal, distance })
+            }
+
+            let axis = if t_max.x < t_max.y && t_max.x < t_max.z {0} else if t_max.y < t_max.z {1} else {2};
+
+            distance = t_max[axis];
+            t_max[axis] += delta[axis];
+            position[axis] += step[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+
+        None
     }
 
     /// Returns normals and a combined collision.
@@ -539,6 +1088,77 @@ impl LoadedChunks {
 }
  
 
+/// The chunks map generation has to finish before actors get spawned.
+#[derive(Deref, DerefMut, Default)]
+pub struct MapGenTarget(Vec<IVec3>);
+
+/// Where the player gets spawned.
+#[derive(Deref, DerefMut, Clone, Copy, Debug)]
+pub struct SpawnPoint(pub Vec3);
+
+#[derive(Deref, DerefMut, Clone, Copy, Debug)]
+pub struct WorldSeed(pub u64);
+
+/// The materials every chunk shares.
+pub struct ChunkAssets {
+    material: Handle<StandardMaterial>,
+    translucent_material: Handle<StandardMaterial>,
+}
+impl FromWorld for ChunkAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut material = StandardMaterial::from(Color::rgb(0.0, 0.0, 0.0));
+        material.perceptual_roughness = 0.9;
+
+        let mut translucent_material = StandardMaterial::from(Color::rgba(0.3, 0.5, 0.8, 0.5));
+        translucent_material.perceptual_roughness = 0.9;
+        translucent_material.alpha_mode = AlphaMode::Blend;
+
+        // Without a renderer (the server) these are never used, so default handles do.
+        match world.get_resource_mut::<Assets<StandardMaterial>>() {
+            Some(mut materials) => Self {
+                material: materials.add(material),
+                translucent_material: materials.add(translucent_material),
+            },
+            None => Self {
+                material: Handle::default(),
+                translucent_material: Handle::default(),
+            },
+        }
+    }
+}
+
+/// How tall the world is. Generation fills it from the bottom, and blocks outside it can't be edited.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WorldConfig {
+    // The lowest block in the world
+    pub min_height: i32,
+    // The highest block that can be built at
+    pub max_height: i32,
+    // Whether the bottom layer is unbreakable Infinium
+    pub bedrock: bool,
+}
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            min_height: -2 * CHUNK_SIZE as i32,
+            max_height: 2 * CHUNK_SIZE as i32 - 1,
+            bedrock: true,
+        }
+    }
+}
+impl WorldConfig {
+    /// The lowest and highest chunk y indices the world covers.
+    pub fn chunk_range(&self) -> (i32, i32) {
+        (self.min_height.div_euclid(CHUNK_SIZE as i32), self.max_height.div_euclid(CHUNK_SIZE as i32))
+    }
+
+    /// Whether the block at this position can be changed. Nothing above max_height, below min_height or in the bedrock can be.
+    pub fn is_editable(&self, position: IVec3) -> bool {
+        let floor = if self.bedrock {self.min_height + 1} else {self.min_height};
+        position.y >= floor && position.y <= self.max_height
+    }
+}
+
 // TODO: We should create a system which reads filenames to determine automatically what textures to use
 // Or, we could just use a texture atlas and have preset coordinates.
 // We could also stitch textures together into a single atlas on startup
@@ -0,0 +1,109 @@
+use std::fs;
+
+use bevy::prelude::*;
+use ndarray::Array3;
+use serde::{Serialize, Deserialize};
+
+use crate::{map::{LoadedChunks, Block, WorldConfig}, coords::CHUNK_SIZE};
+
+
+// Consts
+pub const SAVE_PATH: &str = "world.save";
+// Every save file starts with this
+const SAVE_MAGIC: [u8; 4] = *b"ZGSV";
+// Bumped whenever SavedWorld changes, so old saves are refused instead of misread.
+const SAVE_VERSION: u32 = 2;
+
+// Data
+#[derive(Serialize, Deserialize)]
+pub struct SavedChunk {
+    pub index: IVec3,
+    pub blocks: Vec<Block>,
+}
+impl SavedChunk {
+    /// Returns None if the saved chunk is the wrong size, ie. saved with different chunk dimensions.
+    pub fn to_array(&self) -> Option<Array3<Block>> {
+        Array3::from_shape_vec((CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE), self.blocks.clone()).ok()
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveHeader {
+    magic: [u8; 4],
+    version: u32,
+}
+
+// Resources
+/// A whole world on disk. Inserted as a resource when loading, and picked up by map_setup.
+#[derive(Serialize, Deserialize)]
+pub struct SavedWorld {
+    magic: [u8; 4],
+    version: u32,
+    pub seed: u64,
+    pub config: WorldConfig,
+    pub player_position: Vec3,
+    pub chunks: Vec<SavedChunk>,
+}
+impl SavedWorld {
+    pub fn new(seed: u64, config: WorldConfig, player_position: Vec3, loaded_chunks: &LoadedChunks) -> Self {
+        let chunks = loaded_chunks.iter()
+            .map(|(index, chunk)| SavedChunk { index: *index, blocks: chunk.blocks().iter().collect() })
+            .collect();
+
+        Self { magic: SAVE_MAGIC, version: SAVE_VERSION, seed, config, player_position, chunks }
+    }
+
+    pub fn exists() -> bool {
+        fs::metadata(SAVE_PATH).is_ok()
+    }
+
+    pub fn load() -> Option<Self> {
+        let bytes = match fs::read(SAVE_PATH) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                warn!("Couldn't read {}: {}", SAVE_PATH, error);
+                return None;
+            }
+        };
+
+        // Only the start of the file, to see what kind of save it is
+        let header: SaveHeader = match bincode::deserialize(&bytes) {
+            Ok(header) => header,
+            Err(error) => {
+                warn!("Couldn't parse {}: {}", SAVE_PATH, error);
+                return None;
+            }
+        };
+
+        if header.magic != SAVE_MAGIC {
+            warn!("{} isn't a saved world", SAVE_PATH);
+            return None;
+        }
+
+        if header.version != SAVE_VERSION {
+            warn!("{} was saved with save version {}, but this is version {}", SAVE_PATH, header.version, SAVE_VERSION);
+            return None;
+        }
+
+        match bincode::deserialize(&bytes) {
+            Ok(saved) => Some(saved),
+            Err(error) => {
+                warn!("Couldn't parse {}: {}", SAVE_PATH, error);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        match bincode::serialize(self) {
+            Ok(bytes) => {
+                if let Err(error) = fs::write(SAVE_PATH, bytes) {
+                    warn!("Couldn't write {}: {}", SAVE_PATH, error);
+                }
+            }
+            Err(error) => {
+                warn!("Couldn't serialize world: {}", error);
+            }
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use bevy::utils::HashMap;
+use ndarray::Array3;
+
+use crate::{map::Block, coords::{LocalPos, CHUNK_SIZE, CHUNK_VOLUME}};
+
+
+// Data
+/// A chunk's blocks, palette compressed.
+///
+/// Each distinct block (ignoring damage) goes in the palette once, and every position stores an index into it,
+/// bit-packed as tightly as the palette allows. A chunk that's all one block has a one entry palette and no indices at all.
+/// Damage is rare, so it's kept separately for just the blocks that have some.
+///
+/// Positions are stored in `LocalPos::linearize` order, x fastest, which is what the mesher wants.
+/// Dense `Array3`s and `iter` are indexed [x, y, z] like everywhere else.
+#[derive(Clone, Debug)]
+pub struct ChunkStorage {
+    palette: Vec<Block>,
+    // How many positions point at each palette entry, so unused ones can be spotted without a scan
+    counts: Vec<u32>,
+    indices: PackedIndices,
+    damage: HashMap<u32, f32>,
+}
+impl ChunkStorage {
+    pub fn filled(block: Block) -> Self {
+        let mut storage = Self {
+            palette: vec![block.undamaged()],
+            counts: vec![CHUNK_VOLUME as u32],
+            indices: PackedIndices::new(0),
+            damage: HashMap::default(),
+        };
+
+        if block.damage() != 0.0 {
+            storage.damage = (0..CHUNK_VOLUME as u32).map(|position| (position, block.damage())).collect();
+        }
+
+        storage
+    }
+
+    pub fn from_dense(blocks: &Array3<Block>) -> Self {
+        let mut storage = Self::filled(blocks.iter().next().copied().unwrap_or_default());
+
+        for ((x, y, z), block) in blocks.indexed_iter() {
+            storage.set(LocalPos::from([x, y, z]), *block);
+        }
+
+        storage
+    }
+
+    /// A full copy of every block, for anything that wants to walk the whole chunk quickly, like meshing.
+    pub fn to_dense(&self) -> Array3<Block> {
+        Array3::from_shape_fn((CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE), |(x, y, z)| self.get(LocalPos::from([x, y, z])))
+    }
+
+    /// Every block in `LocalPos::linearize` order, ready to hand to block_mesh.
+    pub fn to_linear(&self) -> Vec<Block> {
+        (0..CHUNK_VOLUME).map(|position| self.get_linear(position)).collect()
+    }
+
+    pub fn get(&self, local: LocalPos) -> Block {
+        self.get_linear(local.linearize())
+    }
+
+    pub fn set(&mut self, local: LocalPos, block: Block) {
+        self.set_linear(local.linearize(), block);
+    }
+
+    /// Every block in the chunk, in Array3 order.
+    pub fn iter(&self) -> impl Iterator<Item = Block> + '_ {
+        (0..CHUNK_SIZE).flat_map(move |x| {
+            (0..CHUNK_SIZE).flat_map(move |y| {
+                (0..CHUNK_SIZE).map(move |z| self.get(LocalPos::from([x, y, z])))
+            })
+        })
+    }
+
+    /// Every different kind of block in the chunk, ignoring damage. Might include blocks that have since been replaced.
+    pub fn palette(&self) -> &[Block] {
+        &self.palette
+    }
+
+    /// The block filling the whole chunk, if it's all one. Still works when old entries are hanging around in the palette.
+    pub fn uniform(&self) -> Option<Block> {
+        if !self.damage.is_empty() {
+            return None;
+        }
+
+        self.counts.iter()
+            .position(|count| *count as usize == CHUNK_VOLUME)
+            .map(|entry| self.palette[entry])
+    }
+
+    /// Roughly how many bytes this chunk's blocks take up.
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.palette.capacity() * std::mem::size_of::<Block>()
+            + self.counts.capacity() * std::mem::size_of::<u32>()
+            + self.indices.words.capacity() * std::mem::size_of::<u64>()
+            + self.damage.capacity() * (std::mem::size_of::<u32>() + std::mem::size_of::<f32>())
+    }
+
+    fn get_linear(&self, position: usize) -> Block {
+        let mut block = self.palette[self.indices.get(position)];
+        if let Some(damage) = self.damage.get(&(position as u32)) {
+            block.set_damage(*damage);
+        }
+        block
+    }
+
+    fn set_linear(&mut self, position: usize, block: Block) {
+        if block.damage() != 0.0 {
+            self.damage.insert(position as u32, block.damage());
+        }
+        else {
+            self.damage.remove(&(position as u32));
+        }
+
+        let undamaged = block.undamaged();
+        let entry = match self.palette.iter().position(|existing| *existing == undamaged) {
+            Some(entry) => entry,
+            None => {
+                if self.palette.len() == self.indices.capacity() {
+                    self.make_room();
+                }
+                self.palette.push(undamaged);
+                self.counts.push(0);
+                self.palette.len() - 1
+            }
+        };
+
+        let old_entry = self.indices.get(position);
+        if old_entry != entry {
+            self.counts[old_entry] -= 1;
+            self.counts[entry] += 1;
+            self.indices.set(position, entry);
+        }
+    }
+
+    /// Makes room for one more palette entry. Drops entries that aren't used anymore first, and only widens the indices if that wasn't enough.
+    fn make_room(&mut self) {
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(self.palette.len());
+        let mut counts = Vec::with_capacity(self.palette.len());
+        for (entry, block) in self.palette.iter().enumerate() {
+            if self.counts[entry] > 0 {
+                remap[entry] = palette.len();
+                palette.push(*block);
+                counts.push(self.counts[entry]);
+            }
+        }
+
+        let mut bits = self.indices.bits;
+        while (1 << bits) < palette.len() + 1 {
+            bits = if bits == 0 {1} else {bits * 2};
+        }
+
+        let mut indices = PackedIndices::new(bits);
+        for position in 0..CHUNK_VOLUME {
+            indices.set(position, remap[self.indices.get(position)]);
+        }
+
+        self.palette = palette;
+        self.counts = counts;
+        self.indices = indices;
+    }
+}
+
+/// Palette indices packed into u64s. Bit widths are powers of two so an index never straddles two words.
+#[derive(Clone, Debug)]
+struct PackedIndices {
+    bits: usize,
+    words: Vec<u64>,
+}
+impl PackedIndices {
+    fn new(bits: usize) -> Self {
+        let words = if bits == 0 {0} else {(CHUNK_VOLUME * bits + 63) / 64};
+        Self { bits, words: vec![0; words] }
+    }
+
+    /// How many palette entries these indices can point at.
+    fn capacity(&self) -> usize {
+        1 << self.bits
+    }
+
+    fn get(&self, position: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+
+        let bit = position * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        ((self.words[bit / 64] >> (bit % 64)) & mask) as usize
+    }
+
+    fn set(&mut self, position: usize, value: usize) {
+        if self.bits == 0 {
+            return;
+        }
+
+        let bit = position * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        let word = &mut self.words[bit / 64];
+        *word = (*word & !(mask << (bit % 64))) | ((value as u64 & mask) << (bit % 64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{BlockType, FLUID_SOURCE};
+
+    // Enough different blocks to need 8 bit indices, counting the air the chunk starts as.
+    fn distinct_blocks() -> Vec<Block> {
+        let mut blocks = vec![
+            Block::new(BlockType::Stone),
+            Block::new(BlockType::Dirt),
+            Block::new(BlockType::Sand),
+            Block::new(BlockType::Gravel),
+            Block::new(BlockType::GoldOre),
+            Block::new(BlockType::Glass),
+            Block::new(BlockType::Leaves),
+        ];
+        for level in 1..=FLUID_SOURCE {
+            blocks.push(Block::new_fluid(BlockType::Water, level));
+            blocks.push(Block::new_fluid(BlockType::Lava, level));
+        }
+        blocks
+    }
+
+    fn assert_matches(storage: &ChunkStorage, expected: &[Block]) {
+        for (position, block) in expected.iter().enumerate() {
+            assert_eq!(storage.get_linear(position), *block, "position {}", position);
+        }
+    }
+
+    #[test]
+    fn grows_through_every_width() {
+        let blocks = distinct_blocks();
+        let mut storage = ChunkStorage::filled(Block::new(BlockType::Air));
+        let mut expected = vec![Block::new(BlockType::Air); CHUNK_VOLUME];
+        let mut widths = vec![storage.indices.bits];
+
+        for (stage, block) in blocks.iter().enumerate() {
+            // Each new block takes every nth position, so earlier ones are still in use when the next one comes in.
+            for position in (stage..CHUNK_VOLUME).step_by(blocks.len()) {
+                storage.set_linear(position, *block);
+                expected[position] = *block;
+            }
+
+            assert_matches(&storage, &expected);
+            if widths.last() != Some(&storage.indices.bits) {
+                widths.push(storage.indices.bits);
+            }
+        }
+
+        assert_eq!(widths, vec![0, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn make_room_keeps_blocks_after_dropping_entries() {
+        let blocks = distinct_blocks();
+        let mut storage = ChunkStorage::filled(Block::new(BlockType::Air));
+        let mut expected = vec![Block::new(BlockType::Air); CHUNK_VOLUME];
+
+        // Every block gets placed then taken away again, so most of the palette goes stale before it fills up.
+        for (position, block) in blocks.iter().enumerate() {
+            storage.set_linear(position, *block);
+            storage.set_linear(position, Block::new(BlockType::Air));
+        }
+        for (position, block) in blocks.iter().enumerate().skip(blocks.len() / 2) {
+            storage.set_linear(position, *block);
+            expected[position] = *block;
+        }
+
+        assert_matches(&storage, &expected);
+        assert!(storage.indices.bits < 8);
+    }
+
+    #[test]
+    fn damage_round_trips() {
+        let mut storage = ChunkStorage::filled(Block::new(BlockType::Stone));
+
+        let mut damaged = Block::new(BlockType::Dirt);
+        damaged.set_damage(0.75);
+        storage.set(LocalPos::new(1, 2, 3), damaged);
+
+        let mut scratched = Block::new(BlockType::Stone);
+        scratched.set_damage(0.25);
+        storage.set(LocalPos::new(4, 5, 6), scratched);
+
+        assert_eq!(storage.get(LocalPos::new(1, 2, 3)), damaged);
+        assert_eq!(storage.get(LocalPos::new(4, 5, 6)), scratched);
+        assert_eq!(storage.get(LocalPos::new(0, 0, 0)), Block::new(BlockType::Stone));
+        assert_eq!(storage.uniform(), None);
+
+        // Fixing the block clears its damage.
+        storage.set(LocalPos::new(1, 2, 3), Block::new(BlockType::Stone));
+        storage.set(LocalPos::new(4, 5, 6), Block::new(BlockType::Stone));
+        assert_eq!(storage.get(LocalPos::new(4, 5, 6)).damage(), 0.0);
+        assert_eq!(storage.uniform(), Some(Block::new(BlockType::Stone)));
+
+        let dense = storage.to_dense();
+        assert_eq!(ChunkStorage::from_dense(&dense).to_dense(), dense);
+    }
+
+    #[test]
+    fn dug_out_chunk_is_uniform_again() {
+        let mut storage = ChunkStorage::filled(Block::new(BlockType::Stone));
+        storage.set_linear(0, Block::new(BlockType::Dirt));
+        assert_eq!(storage.uniform(), None);
+
+        for position in 0..CHUNK_VOLUME {
+            storage.set_linear(position, Block::new(BlockType::Air));
+        }
+
+        assert!(storage.palette().len() > 1);
+        assert_eq!(storage.uniform(), Some(Block::new(BlockType::Air)));
+    }
+
+    #[test]
+    fn smaller_than_dense() {
+        // Something like a chunk of terrain: stone with some ore, dirt on top, then air.
+        let blocks = Array3::from_shape_fn((CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE), |(x, y, z)| {
+            match y {
+                y if y < CHUNK_SIZE / 2 && (x + z) % 7 == 0 => Block::new(BlockType::GoldOre),
+                y if y < CHUNK_SIZE / 2 => Block::new(BlockType::Stone),
+                y if y < CHUNK_SIZE / 2 + 2 => Block::new(BlockType::Dirt),
+                _ => Block::new(BlockType::Air),
+            }
+        });
+
+        let storage = ChunkStorage::from_dense(&blocks);
+        let dense = blocks.len() * std::mem::size_of::<Block>();
+
+        assert_eq!(storage.to_dense(), blocks);
+        assert!(storage.memory_usage() * 10 <= dense, "{} bytes packed, {} dense", storage.memory_usage(), dense);
+    }
+}
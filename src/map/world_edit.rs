@@ -0,0 +1,353 @@
+use bevy::prelude::*;
+use block_mesh::ndshape::{RuntimeShape, Shape};
+use iyes_loopless::prelude::*;
+use ndcopy::copy3;
+
+use crate::{
+    GameState,
+    map::{LoadedChunks, Block, BlockType, SetBlockEvent, SetBlockShape, WorldConfig, WithinBoxIterator},
+    coords::{WorldPos, ChunkPos, ChunkShape, CHUNK_EXTENT},
+    console::{Console, AppConsoleExt, CommandResult, find_player, box_size, check_volume, usage, parse_block_position, parse_block_type},
+    keybinds::{Keybinds, BindInputs},
+    player::{self, LookTarget},
+};
+
+
+// Consts
+// Edits older than this fall off the bottom of the undo stack
+const MAX_UNDO: usize = 32;
+
+// Plugin
+/// Area edits for building: pick two corners, then fill, replace or hollow out the box between them, copy and paste it, and undo any of it.
+/// Everything's done from the console, and each edit goes out as one frame's worth of SetBlockEvents so chunks only get remeshed once.
+#[derive(Default)]
+pub struct WorldEditPlugin;
+impl Plugin for WorldEditPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .init_resource::<WorldEdit>()
+
+         .add_console_command("pos1", "pos1 [<x> <y> <z>]", pos1)
+         .add_console_command("pos2", "pos2 [<x> <y> <z>]", pos2)
+         .add_console_command("set", "set <block>", set)
+         .add_console_command("replace", "replace <from> <to>", replace)
+         .add_console_command("hollow", "hollow <block>", hollow)
+         .add_console_command("copy", "copy", copy)
+         .add_console_command("paste", "paste [-a]", paste)
+         .add_console_command("undo", "undo", undo)
+         .add_console_command("redo", "redo", redo)
+
+         .add_enter_system(GameState::MainMenu, reset_world_edit)
+         .add_system(
+             select_corners
+                 .run_in_state(GameState::Playing)
+                 .after(player::update_look_target)
+         );
+
+    }
+}
+
+// Systems
+/// Undo history and selections only make sense for the world they were made in.
+fn reset_world_edit (
+    mut world_edit: ResMut<WorldEdit>,
+) {
+    *world_edit = WorldEdit::default();
+}
+
+/// Sets a corner of the selection to the block being looked at.
+fn select_corners (
+    inputs: BindInputs,
+    keybinds: Res<Keybinds>,
+    look_target: Res<LookTarget>,
+    mut world_edit: ResMut<WorldEdit>,
+    mut console: ResMut<Console>,
+) {
+    // The binds are probably being typed
+    if console.open {
+        return;
+    }
+
+    let corner = if inputs.just_pressed(&keybinds.select_first) {
+        0
+    }
+    else if inputs.just_pressed(&keybinds.select_second) {
+        1
+    }
+    else {
+        return;
+    };
+
+    match **look_target {
+        Some(hit) => {
+            world_edit.corners[corner] = Some(hit.position);
+            console.print(describe_corner(&world_edit, corner));
+        }
+        None => console.print("Not looking at a block"),
+    }
+}
+
+// Commands
+fn pos1(world: &mut World, args: &[&str]) -> CommandResult {
+    select_corner(world, args, 0)
+}
+
+fn pos2(world: &mut World, args: &[&str]) -> CommandResult {
+    select_corner(world, args, 1)
+}
+
+fn set(world: &mut World, args: &[&str]) -> CommandResult {
+    let block_type = match args {
+        [block] => parse_block_type(block)?,
+        _ => return Err(usage("set <block>")),
+    };
+
+    let (min, max) = selection(world)?;
+    let changed = apply_edit(world, WithinBoxIterator::new(min, max).map(|position| (position, Block::new(block_type))));
+
+    Ok(format!("Set {} blocks to {:?}", changed, block_type))
+}
+
+fn replace(world: &mut World, args: &[&str]) -> CommandResult {
+    let (from, to) = match args {
+        [from, to] => (parse_block_type(from)?, parse_block_type(to)?),
+        _ => return Err(usage("replace <from> <to>")),
+    };
+
+    let (min, max) = selection(world)?;
+    let loaded_chunks = world.resource::<LoadedChunks>();
+    let changes: Vec<(IVec3, Block)> = WithinBoxIterator::new(min, max)
+        .filter(|position| loaded_chunks.get_block(*position).map_or(false, |block| block.block_type() == from))
+        .map(|position| (position, Block::new(to)))
+        .collect();
+
+    let changed = apply_edit(world, changes);
+    Ok(format!("Replaced {} {:?} with {:?}", changed, from, to))
+}
+
+/// Makes the selection a box with walls of the block, and air inside.
+fn hollow(world: &mut World, args: &[&str]) -> CommandResult {
+    let block_type = match args {
+        [block] => parse_block_type(block)?,
+        _ => return Err(usage("hollow <block>")),
+    };
+
+    let (min, max) = selection(world)?;
+    let changes = WithinBoxIterator::new(min, max).map(|position| {
+        let on_shell = position.cmpeq(min).any() || position.cmpeq(max).any();
+        (position, Block::new(if on_shell {block_type} else {BlockType::Air}))
+    });
+
+    let changed = apply_edit(world, changes);
+    Ok(format!("Hollowed out the selection, changing {} blocks", changed))
+}
+
+/// Copies the selection, remembering where it was relative to the player so pasting puts it back the same way round them.
+fn copy(world: &mut World, _args: &[&str]) -> CommandResult {
+    let (min, max) = selection(world)?;
+    let player = player_block(world).ok_or("no player to copy relative to")?;
+
+    let clipboard = Clipboard::copy(world.resource::<LoadedChunks>(), min, max, min - player);
+    let size = clipboard.size;
+    world.resource_mut::<WorldEdit>().clipboard = Some(clipboard);
+
+    Ok(format!("Copied {}x{}x{} blocks", size.x, size.y, size.z))
+}
+
+/// `-a` leaves out the air, so whatever's already there shows through the gaps.
+fn paste(world: &mut World, args: &[&str]) -> CommandResult {
+    let skip_air = match args {
+        [] => false,
+        ["-a"] => true,
+        _ => return Err(usage("paste [-a]")),
+    };
+
+    let player = player_block(world).ok_or("no player to paste relative to")?;
+    let changes: Vec<(IVec3, Block)> = match &world.resource::<WorldEdit>().clipboard {
+        Some(clipboard) => clipboard.blocks_at(player + clipboard.offset)
+            .filter(|(_, block)| !skip_air || block.block_type() != BlockType::Air)
+            .collect(),
+        None => return Err("nothing copied yet".to_string()),
+    };
+
+    let changed = apply_edit(world, changes);
+    Ok(format!("Pasted {} blocks", changed))
+}
+
+fn undo(world: &mut World, _args: &[&str]) -> CommandResult {
+    let diff = world.resource_mut::<WorldEdit>().undo.pop().ok_or("nothing to undo")?;
+
+    send_blocks(world, diff.iter().map(|change| (change.position, change.before)));
+    let count = diff.len();
+    world.resource_mut::<WorldEdit>().redo.push(diff);
+
+    Ok(format!("Undid {} blocks", count))
+}
+
+fn redo(world: &mut World, _args: &[&str]) -> CommandResult {
+    let diff = world.resource_mut::<WorldEdit>().redo.pop().ok_or("nothing to redo")?;
+
+    send_blocks(world, diff.iter().map(|change| (change.position, change.after)));
+    let count = diff.len();
+    world.resource_mut::<WorldEdit>().undo.push(diff);
+
+    Ok(format!("Redid {} blocks", count))
+}
+
+// Helper functions
+fn select_corner(world: &mut World, args: &[&str], corner: usize) -> CommandResult {
+    let position = match args {
+        [] => {
+            let look_target = world.resource::<LookTarget>();
+            look_target.map(|hit| hit.position).ok_or("not looking at a block")?
+        }
+        [x, y, z] => parse_block_position(world, [x, y, z])?,
+        _ => return Err(usage(if corner == 0 {"pos1 [<x> <y> <z>]"} else {"pos2 [<x> <y> <z>]"})),
+    };
+
+    let mut world_edit = world.resource_mut::<WorldEdit>();
+    world_edit.corners[corner] = Some(position);
+
+    Ok(describe_corner(&world_edit, corner))
+}
+
+fn describe_corner(world_edit: &WorldEdit, corner: usize) -> String {
+    let position = world_edit.corners[corner].unwrap_or_default();
+
+    match world_edit.selection() {
+        Some((min, max)) => {
+            let [x, y, z] = box_size(min, max);
+            format!("Corner {} at {}, selecting {}x{}x{}", corner + 1, position, x, y, z)
+        }
+        None => format!("Corner {} at {}", corner + 1, position),
+    }
+}
+
+/// The selection's corners, if it's small enough to edit.
+fn selection(world: &World) -> Result<(IVec3, IVec3), String> {
+    let (min, max) = world.resource::<WorldEdit>().selection().ok_or("select both corners first, with pos1 and pos2")?;
+    check_volume(min, max)?;
+
+    Ok((min, max))
+}
+
+fn player_block(world: &mut World) -> Option<IVec3> {
+    let player = find_player(world)?;
+    world.get::<Transform>(player).map(|transform| transform.translation.round().as_ivec3())
+}
+
+/// Sends the changes that would actually change something, and puts them on the undo stack. Returns how many there were.
+/// Anything unloaded or outside the world's height is left alone.
+fn apply_edit(world: &mut World, changes: impl IntoIterator<Item = (IVec3, Block)>) -> usize {
+    let loaded_chunks = world.resource::<LoadedChunks>();
+    let config = world.resource::<WorldConfig>();
+
+    let diff: Vec<BlockChange> = changes.into_iter()
+        .filter(|(position, _)| config.is_editable(*position))
+        .filter_map(|(position, after)| {
+            let before = loaded_chunks.get_block(position)?;
+            (before != after).then(|| BlockChange { position, before, after })
+        })
+        .collect();
+
+    if diff.is_empty() {
+        return 0;
+    }
+
+    send_blocks(world, diff.iter().map(|change| (change.position, change.after)));
+    let count = diff.len();
+
+    let mut world_edit = world.resource_mut::<WorldEdit>();
+    world_edit.undo.push(diff);
+    if world_edit.undo.len() > MAX_UNDO {
+        world_edit.undo.remove(0);
+    }
+    world_edit.redo.clear();
+
+    count
+}
+
+/// All in the same frame, so each chunk they touch is only remeshed once.
+fn send_blocks(world: &mut World, blocks: impl Iterator<Item = (IVec3, Block)>) {
+    let mut events = world.resource_mut::<Events<SetBlockEvent>>();
+    for (position, block) in blocks {
+        events.send(SetBlockEvent { shape: SetBlockShape::Block(position), block });
+    }
+}
+
+// Resources
+#[derive(Default)]
+pub struct WorldEdit {
+    pub corners: [Option<IVec3>; 2],
+    pub clipboard: Option<Clipboard>,
+    undo: Vec<Vec<BlockChange>>,
+    redo: Vec<Vec<BlockChange>>,
+}
+impl WorldEdit {
+    /// The lowest and highest corners of the selection, once both have been picked.
+    pub fn selection(&self) -> Option<(IVec3, IVec3)> {
+        match self.corners {
+            [Some(first), Some(second)] => Some((first.min(second), first.max(second))),
+            _ => None,
+        }
+    }
+}
+
+// Data
+/// A copied box of blocks, flat with x varying fastest.
+pub struct Clipboard {
+    pub size: UVec3,
+    // From the player to the box's lowest corner, when it was copied
+    pub offset: IVec3,
+    blocks: Vec<Block>,
+}
+impl Clipboard {
+    /// Copies from min to max, inclusive. Unloaded blocks come out as air.
+    pub fn copy(loaded_chunks: &LoadedChunks, min: IVec3, max: IVec3, offset: IVec3) -> Self {
+        let size = (max - min + IVec3::ONE).as_uvec3();
+        let shape = RuntimeShape::<u32, 3>::new(size.to_array());
+        let mut blocks = vec![Block::new(BlockType::Air); shape.size() as usize];
+
+        // A chunk at a time, copying just the part that overlaps the box
+        for index in WithinBoxIterator::new(*WorldPos(min).chunk(), *WorldPos(max).chunk()) {
+            let chunk = match loaded_chunks.get(&index) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+
+            let origin = *ChunkPos(index).origin();
+            let start = min.max(origin);
+            let end = max.min(origin + CHUNK_EXTENT - IVec3::ONE);
+
+            copy3(
+                (end - start + IVec3::ONE).as_uvec3().to_array(),
+                &chunk.blocks().to_linear(),
+                &ChunkShape {},
+                (start - origin).as_uvec3().to_array(),
+                &mut blocks,
+                &shape,
+                (start - min).as_uvec3().to_array(),
+            );
+        }
+
+        Self { size, offset, blocks }
+    }
+
+    /// Every block, placed with its lowest corner at `origin`.
+    pub fn blocks_at(&self, origin: IVec3) -> impl Iterator<Item = (IVec3, Block)> + '_ {
+        let shape = RuntimeShape::<u32, 3>::new(self.size.to_array());
+
+        self.blocks.iter().enumerate().map(move |(i, block)| {
+            let local = UVec3::from(shape.delinearize(i as u32)).as_ivec3();
+            (origin + local, *block)
+        })
+    }
+}
+
+/// One block an edit changed, so it can be put back.
+#[derive(Clone, Copy, Debug)]
+struct BlockChange {
+    position: IVec3,
+    before: Block,
+    after: Block,
+}
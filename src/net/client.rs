@@ -0,0 +1,394 @@
+use std::{collections::VecDeque, net::{SocketAddr, Ipv4Addr}, io};
+
+use bevy::{prelude::*, ecs::event::{Events, ManualEventReader}, utils::{HashMap, HashSet}};
+use iyes_loopless::prelude::*;
+
+use crate::{
+    GameState,
+    actions::MoveIntent,
+    map::{SetBlockEvent, LoadChunkEvent, MapGenTarget, SpawnPoint, WorldSeed},
+    net::{NetSocket, NetId, NetEdit, ClientMessage, ServerMessage, InputFrame, Snapshot, ChunkAssembly, DEFAULT_PORT, MAX_EDITS_PER_PACKET, MAX_INPUTS_PER_PACKET, TIMEOUT},
+    physics::{Velocity, StepTime},
+    player::Player,
+};
+
+
+// Consts
+// Seconds between Hellos while waiting to hear back from the server
+const HELLO_INTERVAL: f64 = 0.5;
+// How far our prediction can drift from the server before we get pulled back in line
+const CORRECTION_THRESHOLD: f32 = 0.05;
+// How many frames of inputs and predictions we hang on to waiting for the server
+const HISTORY_LENGTH: usize = 256;
+
+// Plugin
+#[derive(Default)]
+pub struct NetClientPlugin;
+impl Plugin for NetClientPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .init_resource::<ServerAddress>()
+         .init_resource::<RemoteActorAssets>()
+         .add_enter_system(GameState::MainMenu, disconnect)
+         .add_system(send_hello.run_in_state(GameState::Connecting).run_if(connected))
+         .add_system(receive.run_if(connected))
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .run_if(connected)
+                 .after("input")
+                 .before("physics")
+                 .with_system(send_input)
+                 .into()
+         )
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .run_if(connected)
+                 .after("physics")
+                 .with_system(record_prediction)
+                 .with_system(reconcile.after(record_prediction))
+                 .into()
+         );
+
+    }
+}
+
+// Run criteria
+pub fn connected (
+    client: Option<Res<NetClient>>,
+) -> bool {
+    client.is_some()
+}
+
+// Systems
+fn send_hello (
+    mut client: ResMut<NetClient>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+
+    if now - client.last_hello > HELLO_INTERVAL {
+        client.last_hello = now;
+        client.socket.send(client.server, &ClientMessage::Hello);
+    }
+}
+
+fn receive (
+    mut commands: Commands,
+    mut client: ResMut<NetClient>,
+    time: Res<Time>,
+    state: Res<CurrentState<GameState>>,
+    mut target: ResMut<MapGenTarget>,
+
+    mut ev_set_block: ResMut<Events<SetBlockEvent>>,
+    mut ev_load_chunk: EventWriter<LoadChunkEvent>,
+) {
+    let client = &mut *client;
+    let now = time.seconds_since_startup();
+    let playing = state.0 == GameState::Playing;
+
+    // Edits made here since last frame go to the server. Anything else in the world changing is the server's call.
+    for ev in client.edit_reader.iter(&ev_set_block) {
+        if playing {
+            client.outgoing_edits.push_back(NetEdit::from(ev));
+        }
+    }
+
+    while let Some((from, message)) = client.socket.receive::<ServerMessage>() {
+        if from != client.server {
+            continue;
+        }
+        client.last_heard = now;
+
+        match message {
+            ServerMessage::Welcome { actor, seed, config, spawn, chunks, first_edit } => {
+                if state.0 != GameState::Connecting {
+                    continue;
+                }
+
+                info!("Connected to {} as actor {}", client.server, actor);
+                client.actor = Some(actor);
+                client.next_edit = first_edit;
+
+                **target = chunks;
+                commands.insert_resource(WorldSeed(seed));
+                commands.insert_resource(config);
+                commands.insert_resource(SpawnPoint(spawn));
+                commands.insert_resource(NextState(GameState::MapGen));
+            }
+            ServerMessage::ChunkPart { index, start, runs } => {
+                if !client.loaded_chunks.contains(&index) {
+                    let assembly = client.assembling.entry(index).or_default();
+                    assembly.add_runs(start, &runs);
+
+                    match assembly.finish() {
+                        Some(blocks) => {
+                            ev_load_chunk.send(LoadChunkEvent { index, blocks });
+                            client.assembling.remove(&index);
+                            client.loaded_chunks.insert(index);
+                        }
+                        None => continue,
+                    }
+                }
+
+                // Acknowledged every time, in case the last one got lost.
+                client.socket.send(client.server, &ClientMessage::AckChunk(index));
+            }
+            ServerMessage::Edits { first, edits } => {
+                // Chunks might still be on their way until we're playing, so wait for the server to send these again.
+                if !playing {
+                    continue;
+                }
+
+                for (sequence, edit) in (first..).zip(edits) {
+                    if sequence == client.next_edit {
+                        ev_set_block.send(edit.to_event());
+                        client.next_edit += 1;
+                    }
+                }
+            }
+            ServerMessage::Snapshot(snapshot) => {
+                let newer = client.snapshot.as_ref().map_or(true, |current| snapshot.input_ack >= current.input_ack);
+                if newer {
+                    client.snapshot = Some(snapshot);
+                }
+            }
+        }
+    }
+
+    // Skip past the server's edits we just sent so they don't get sent back.
+    client.edit_reader.iter(&ev_set_block).for_each(drop);
+
+    if now - client.last_heard > TIMEOUT {
+        warn!("Lost connection to {}", client.server);
+        commands.insert_resource(NextState(GameState::MainMenu));
+    }
+}
+
+/// Sends this frame's input, along with everything the server hasn't acknowledged yet.
+fn send_input (
+    mut client: ResMut<NetClient>,
+    time: Res<StepTime>,
+
+    query: Query<(&Transform, &MoveIntent), With<Player>>,
+) {
+    let client = &mut *client;
+
+    if let Ok((transform, intent)) = query.get_single() {
+        client.tick += 1;
+        client.unacked_inputs.push_back(InputFrame {
+            tick: client.tick,
+            dt: time.delta_seconds(),
+            yaw: transform.rotation.to_euler(EulerRot::YXZ).0,
+            intent: *intent,
+        });
+
+        while client.unacked_inputs.len() > HISTORY_LENGTH {
+            client.unacked_inputs.pop_front();
+        }
+    }
+
+    let skip = client.unacked_inputs.len().saturating_sub(MAX_INPUTS_PER_PACKET);
+    let inputs = client.unacked_inputs.iter().skip(skip).copied().collect();
+    client.socket.send(client.server, &ClientMessage::Inputs(inputs));
+
+    if !client.outgoing_edits.is_empty() {
+        let edits = client.outgoing_edits.iter().take(MAX_EDITS_PER_PACKET).copied().collect();
+        client.socket.send(client.server, &ClientMessage::Edits { first: client.first_outgoing_edit, edits });
+    }
+
+    client.socket.send(client.server, &ClientMessage::AckEdits(client.next_edit));
+}
+
+/// Remembers where we ended up after this frame's input, to check against the server later.
+fn record_prediction (
+    mut client: ResMut<NetClient>,
+
+    query: Query<&Transform, With<Player>>,
+) {
+    if let Ok(transform) = query.get_single() {
+        let tick = client.tick;
+        client.predictions.push_back((tick, transform.translation));
+
+        while client.predictions.len() > HISTORY_LENGTH {
+            client.predictions.pop_front();
+        }
+    }
+}
+
+/// Applies the latest snapshot. The server steps our player through the same inputs with the same dt, so our prediction should match it.
+/// If it doesn't, we get corrected by however far off we were, which carries over to every input the server hasn't seen yet.
+/// Everyone else just goes where they're told.
+fn reconcile (
+    mut commands: Commands,
+    mut client: ResMut<NetClient>,
+    assets: Res<RemoteActorAssets>,
+
+    mut player_query: Query<&mut Transform, With<Player>>,
+    mut remote_query: Query<(Entity, &NetId, &mut Transform, &mut Velocity), (With<RemoteActor>, Without<Player>)>,
+) {
+    let client = &mut *client;
+
+    let snapshot = match client.snapshot.take() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+
+    while client.first_outgoing_edit < snapshot.edit_ack && !client.outgoing_edits.is_empty() {
+        client.outgoing_edits.pop_front();
+        client.first_outgoing_edit += 1;
+    }
+
+    client.unacked_inputs.retain(|frame| frame.tick > snapshot.input_ack);
+
+    let predicted = client.predictions.iter()
+        .find(|(tick, _)| *tick == snapshot.input_ack)
+        .map(|(_, translation)| *translation);
+    client.predictions.retain(|(tick, _)| *tick > snapshot.input_ack);
+
+    let mut seen = HashSet::default();
+
+    for actor in snapshot.actors.iter() {
+        seen.insert(actor.id);
+
+        if Some(actor.id) == client.actor {
+            let predicted = match predicted {
+                Some(predicted) => predicted,
+                None => continue,
+            };
+
+            let error = actor.translation - predicted;
+            if error.length() > CORRECTION_THRESHOLD {
+                for mut transform in player_query.iter_mut() {
+                    transform.translation += error;
+                }
+                for (_, translation) in client.predictions.iter_mut() {
+                    *translation += error;
+                }
+            }
+            continue;
+        }
+
+        let existing = remote_query.iter_mut().find(|(_, id, _, _)| id.0 == actor.id);
+
+        match existing {
+            Some((_, _, mut transform, mut velocity)) => {
+                transform.translation = actor.translation;
+                transform.rotation = Quat::from_rotation_y(actor.yaw);
+                // Keeps them moving between snapshots
+                **velocity = actor.velocity;
+            }
+            None => {
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: assets.mesh.clone(),
+                        material: assets.material.clone(),
+                        transform: Transform::from_translation(actor.translation).with_rotation(Quat::from_rotation_y(actor.yaw)),
+                        ..default()
+                    })
+                    .insert(NetId(actor.id))
+                    .insert(RemoteActor)
+                    .insert(Velocity(actor.velocity));
+            }
+        }
+    }
+
+    for (entity, id, _, _) in remote_query.iter() {
+        if !seen.contains(&id.0) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Runs on entering GameState::MainMenu. Says goodbye to the server if we were connected to one.
+fn disconnect (
+    mut commands: Commands,
+    opt_client: Option<Res<NetClient>>,
+
+    query: Query<Entity, With<RemoteActor>>,
+) {
+    if let Some(client) = opt_client {
+        client.socket.send(client.server, &ClientMessage::Goodbye);
+        commands.remove_resource::<NetClient>();
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Resources
+pub struct NetClient {
+    socket: NetSocket,
+    server: SocketAddr,
+    // Our player's id on the server, once it's welcomed us
+    pub actor: Option<u32>,
+    last_hello: f64,
+    last_heard: f64,
+
+    tick: u32,
+    unacked_inputs: VecDeque<InputFrame>,
+    predictions: VecDeque<(u32, Vec3)>,
+    snapshot: Option<Snapshot>,
+
+    // The next server edit we're waiting on
+    next_edit: u32,
+    outgoing_edits: VecDeque<NetEdit>,
+    // Sequence number of the front of outgoing_edits
+    first_outgoing_edit: u32,
+    edit_reader: ManualEventReader<SetBlockEvent>,
+
+    assembling: HashMap<IVec3, ChunkAssembly>,
+    loaded_chunks: HashSet<IVec3>,
+}
+impl NetClient {
+    pub fn connect(server: SocketAddr, now: f64) -> io::Result<Self> {
+        let socket = NetSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)))?;
+
+        Ok(Self {
+            socket,
+            server,
+            actor: None,
+            last_hello: f64::NEG_INFINITY,
+            last_heard: now,
+            tick: 0,
+            unacked_inputs: VecDeque::new(),
+            predictions: VecDeque::new(),
+            snapshot: None,
+            next_edit: 0,
+            outgoing_edits: VecDeque::new(),
+            first_outgoing_edit: 0,
+            edit_reader: ManualEventReader::default(),
+            assembling: HashMap::default(),
+            loaded_chunks: HashSet::default(),
+        })
+    }
+}
+
+/// Where "Join Server" connects to. Set with `--connect <address>`.
+#[derive(Deref, DerefMut, Clone, Copy, Debug)]
+pub struct ServerAddress(pub SocketAddr);
+impl Default for ServerAddress {
+    fn default() -> Self {
+        Self(SocketAddr::from((Ipv4Addr::LOCALHOST, DEFAULT_PORT)))
+    }
+}
+
+pub struct RemoteActorAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+impl FromWorld for RemoteActorAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(Mesh::from(shape::Box::new(0.4, 1.8, 0.4)));
+        let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial::from(Color::rgb(0.2, 0.4, 0.9)));
+
+        Self { mesh, material }
+    }
+}
+
+// Components
+/// Another player, or anything else the server's simulating that isn't us.
+#[derive(Component)]
+pub struct RemoteActor;
@@ -0,0 +1,315 @@
+use std::{io::{self, ErrorKind}, net::{SocketAddr, UdpSocket}};
+
+use bevy::prelude::*;
+use ndarray::Array3;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+
+use crate::{storage::ChunkStorage, coords::{CHUNK_SIZE, CHUNK_VOLUME}, map::{Block, SetBlockEvent, SetBlockShape, WorldConfig}, actions::MoveIntent, client::NetClient};
+
+
+// Consts
+pub const DEFAULT_PORT: u16 = 7878;
+// Kept under a typical MTU so packets don't get fragmented.
+const MAX_PACKET_SIZE: usize = 1200;
+// A run is a block plus a u16, so this keeps chunk packets well under MAX_PACKET_SIZE.
+const MAX_RUNS_PER_PACKET: usize = 80;
+pub const MAX_EDITS_PER_PACKET: usize = 32;
+// Unacknowledged inputs are sent again with every packet, so a lost one doesn't cost a frame of movement.
+pub const MAX_INPUTS_PER_PACKET: usize = 16;
+// Seconds without hearing anything before giving up on the other side.
+pub const TIMEOUT: f64 = 10.0;
+
+// Run criteria
+/// Whether this process gets to decide how the world changes. False when connected to a server,
+/// since fluids, falling blocks and collapses all come from the server's edits instead.
+pub fn has_authority (
+    client: Option<Res<NetClient>>,
+) -> bool {
+    client.is_none()
+}
+
+// Helper functions
+/// Run-length encodes a chunk and splits it into packets.
+pub fn chunk_messages(index: IVec3, blocks: &ChunkStorage) -> Vec<ServerMessage> {
+    let mut messages = Vec::new();
+    let mut runs: Vec<(Block, u16)> = Vec::new();
+    let mut start = 0;
+    let mut position = 0;
+
+    for block in blocks.iter() {
+        match runs.last_mut() {
+            Some((last, count)) if *last == block && *count < u16::MAX => *count += 1,
+            _ => {
+                if runs.len() == MAX_RUNS_PER_PACKET {
+                    messages.push(ServerMessage::ChunkPart { index, start, runs: std::mem::take(&mut runs) });
+                    start = position;
+                }
+                runs.push((block, 1));
+            }
+        }
+        position += 1;
+    }
+
+    if !runs.is_empty() {
+        messages.push(ServerMessage::ChunkPart { index, start, runs });
+    }
+
+    messages
+}
+
+// Data
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ClientMessage {
+    Hello,
+    Inputs(Vec<InputFrame>),
+    Edits { first: u32, edits: Vec<NetEdit> },
+    AckChunk(IVec3),
+    // The next edit sequence number we're waiting on
+    AckEdits(u32),
+    Goodbye,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ServerMessage {
+    Welcome { actor: u32, seed: u64, config: WorldConfig, spawn: Vec3, chunks: Vec<IVec3>, first_edit: u32 },
+    // `start` is the index into the chunk's blocks, in Array3 iteration order, that the first run begins at.
+    ChunkPart { index: IVec3, start: u32, runs: Vec<(Block, u16)> },
+    Edits { first: u32, edits: Vec<NetEdit> },
+    Snapshot(Snapshot),
+}
+
+/// One frame of a client's input.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct InputFrame {
+    pub tick: u32,
+    pub dt: f32,
+    pub yaw: f32,
+    pub intent: MoveIntent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct NetEdit {
+    pub shape: SetBlockShape,
+    pub block: Block,
+}
+impl NetEdit {
+    pub fn to_event(self) -> SetBlockEvent {
+        SetBlockEvent { shape: self.shape, block: self.block }
+    }
+}
+impl From<&SetBlockEvent> for NetEdit {
+    fn from(ev: &SetBlockEvent) -> Self {
+        Self { shape: ev.shape, block: ev.block }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    // The last input tick from this client the server has simulated
+    pub input_ack: u32,
+    // The next edit from this client the server's waiting on
+    pub edit_ack: u32,
+    pub actors: Vec<ActorState>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ActorState {
+    pub id: u32,
+    pub translation: Vec3,
+    pub yaw: f32,
+    pub velocity: Vec3,
+}
+
+/// Collects a chunk's blocks as its packets come in, in whatever order they arrive.
+pub struct ChunkAssembly {
+    blocks: Vec<Option<Block>>,
+    filled: usize,
+}
+impl Default for ChunkAssembly {
+    fn default() -> Self {
+        Self { blocks: vec![None; CHUNK_VOLUME], filled: 0 }
+    }
+}
+impl ChunkAssembly {
+    pub fn add_runs(&mut self, start: u32, runs: &[(Block, u16)]) {
+        let mut position = start as usize;
+
+        for (block, count) in runs {
+            for _ in 0..*count {
+                if let Some(slot) = self.blocks.get_mut(position) {
+                    if slot.is_none() {
+                        self.filled += 1;
+                    }
+                    *slot = Some(*block);
+                }
+                position += 1;
+            }
+        }
+    }
+
+    /// Returns the finished chunk once every block's arrived.
+    pub fn finish(&self) -> Option<Array3<Block>> {
+        if self.filled < CHUNK_VOLUME {
+            return None;
+        }
+
+        let blocks = self.blocks.iter().map(|block| block.unwrap_or_default()).collect();
+        Array3::from_shape_vec((CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE), blocks).ok()
+    }
+}
+
+/// A non-blocking UDP socket that sends and receives bincoded messages.
+pub struct NetSocket {
+    socket: UdpSocket,
+}
+impl NetSocket {
+    pub fn bind(address: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self { socket })
+    }
+
+    pub fn send<T: Serialize>(&self, to: SocketAddr, message: &T) {
+        match bincode::serialize(message) {
+            Ok(bytes) => {
+                if bytes.len() > MAX_PACKET_SIZE {
+                    warn!("Sending a {} byte packet, it might get fragmented", bytes.len());
+                }
+                if let Err(error) = self.socket.send_to(&bytes, to) {
+                    warn!("Couldn't send to {}: {}", to, error);
+                }
+            }
+            Err(error) => {
+                warn!("Couldn't serialize message: {}", error);
+            }
+        }
+    }
+
+    /// Returns the next message waiting on the socket, skipping over anything that doesn't parse.
+    pub fn receive<T: DeserializeOwned>(&self) -> Option<(SocketAddr, T)> {
+        let mut buffer = [0; 65536];
+
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, from)) => {
+                    match bincode::deserialize(&buffer[..length]) {
+                        Ok(message) => return Some((from, message)),
+                        Err(error) => warn!("Ignoring bad packet from {}: {}", from, error),
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return None,
+                // On Windows a previous send to a closed port shows up here, so carry on.
+                Err(error) if error.kind() == ErrorKind::ConnectionReset => continue,
+                Err(error) => {
+                    warn!("Couldn't receive: {}", error);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+// Components
+/// An actor that's replicated over the network. Same on every side.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NetId(pub u32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::BlockType, coords::LocalPos};
+
+    // Neighbouring blocks rarely match, so it takes lots of runs and several packets.
+    fn noisy_chunk() -> ChunkStorage {
+        let block_types = [BlockType::Stone, BlockType::Dirt, BlockType::Air, BlockType::GoldOre, BlockType::Gravel];
+        let mut storage = ChunkStorage::filled(Block::new(BlockType::Air));
+
+        for x in 0..CHUNK_SIZE as u32 {
+            for y in 0..CHUNK_SIZE as u32 {
+                for z in 0..CHUNK_SIZE as u32 {
+                    let noise = (x * 7 + y * 13 + z * 31 + x * y * z) as usize;
+                    storage.set(LocalPos::new(x, y, z), Block::new(block_types[noise % block_types.len()]));
+                }
+            }
+        }
+
+        storage
+    }
+
+    fn chunk_parts(messages: Vec<ServerMessage>) -> Vec<(u32, Vec<(Block, u16)>)> {
+        messages.into_iter()
+            .map(|message| match message {
+                ServerMessage::ChunkPart { start, runs, .. } => (start, runs),
+                other => panic!("expected a chunk part, got {:?}", other),
+            })
+            .collect()
+    }
+
+    fn assert_assembled(assembly: &ChunkAssembly, storage: &ChunkStorage) {
+        let blocks = assembly.finish().expect("every part arrived");
+        assert!(blocks.iter().copied().eq(storage.iter()));
+    }
+
+    #[test]
+    fn noisy_chunk_round_trips_across_packets() {
+        let storage = noisy_chunk();
+        let parts = chunk_parts(chunk_messages(IVec3::ZERO, &storage));
+
+        assert!(parts.len() > 1, "the chunk should need more than one packet");
+        for (_, runs) in parts.iter() {
+            assert!(runs.len() <= MAX_RUNS_PER_PACKET);
+        }
+
+        let mut assembly = ChunkAssembly::default();
+        for (start, runs) in parts.iter() {
+            assembly.add_runs(*start, runs);
+        }
+
+        assert_assembled(&assembly, &storage);
+    }
+
+    #[test]
+    fn parts_out_of_order_and_duplicated() {
+        let storage = noisy_chunk();
+        let parts = chunk_parts(chunk_messages(IVec3::ZERO, &storage));
+        let mut assembly = ChunkAssembly::default();
+
+        // Backwards, with every part arriving twice
+        for (received, (start, runs)) in parts.iter().rev().enumerate() {
+            assembly.add_runs(*start, runs);
+            assembly.add_runs(*start, runs);
+
+            if received + 1 < parts.len() {
+                assert!(assembly.finish().is_none(), "finished with only {} of {} parts", received + 1, parts.len());
+            }
+        }
+
+        assert_assembled(&assembly, &storage);
+
+        // Resending a part after it's done changes nothing
+        let (start, runs) = &parts[0];
+        assembly.add_runs(*start, runs);
+        assert_assembled(&assembly, &storage);
+    }
+
+    #[test]
+    fn unfinished_until_every_part_arrives() {
+        let storage = noisy_chunk();
+        let parts = chunk_parts(chunk_messages(IVec3::ZERO, &storage));
+
+        // Leaving out any one part, even with the rest sent twice, isn't enough.
+        for missing in 0..parts.len() {
+            let mut assembly = ChunkAssembly::default();
+            for _ in 0..2 {
+                for (part, (start, runs)) in parts.iter().enumerate() {
+                    if part != missing {
+                        assembly.add_runs(*start, runs);
+                    }
+                }
+            }
+
+            assert!(assembly.finish().is_none(), "finished without part {}", missing);
+        }
+    }
+}
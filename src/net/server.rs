@@ -0,0 +1,372 @@
+use std::{collections::VecDeque, net::{SocketAddr, Ipv4Addr}, io};
+
+use bevy::{prelude::*, math::Vec3A, utils::HashMap};
+use iyes_loopless::prelude::*;
+
+use crate::{
+    GameState,
+    actions::walk,
+    map::{LoadedChunks, SetBlockEvent, SetBlockShape, SpawnPoint, WorldSeed, WorldConfig},
+    net::{NetSocket, NetId, NetEdit, ClientMessage, ServerMessage, InputFrame, Snapshot, ActorState, chunk_messages, MAX_EDITS_PER_PACKET, TIMEOUT},
+    physics::{AabbCollider, Velocity, Falls, Submerged, Wades, Gravity, InputDriven, SWIM_THRESHOLD, step_gravity, step_velocity},
+    player::REACH,
+};
+
+
+// Consts
+// How many chunks get (re)sent to each client per tick until they're acknowledged
+const CHUNKS_PER_TICK: usize = 4;
+// How many packets of edits each client can be sent per tick
+const EDIT_PACKETS_PER_TICK: usize = 8;
+// Clients report their own frame times, so cap them to stop anyone from moving in huge steps.
+const MAX_INPUT_DT: f32 = 0.1;
+// How many seconds of movement a client can bank up against our clock, so inputs that arrive in bunches still get through.
+// Past that their frames are dropped, otherwise they could send a pile of MAX_INPUT_DT frames every packet.
+const MAX_INPUT_BUDGET: f32 = 0.25;
+// Extra reach allowed on client edits, since their player might have moved a little by the time we see them
+const EDIT_REACH_SLACK: f32 = 2.0;
+
+// Plugin
+/// Accepts clients, simulates their players and keeps their worlds in sync. Needs a NetServer resource.
+#[derive(Default)]
+pub struct NetServerPlugin;
+impl Plugin for NetServerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .add_system(log_edits.after(crate::map::set_block_chunk))
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .label("input")
+                 .before("physics")
+                 .with_system(receive)
+                 .with_system(move_remote_players.after(receive))
+                 .into()
+         )
+         .add_system(
+             send_updates
+                 .run_in_state(GameState::Playing)
+                 .after("physics")
+         );
+
+    }
+}
+
+// Systems
+fn receive (
+    mut commands: Commands,
+    mut server: ResMut<NetServer>,
+    time: Res<Time>,
+    seed: Res<WorldSeed>,
+    config: Res<WorldConfig>,
+    spawn_point: Res<SpawnPoint>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    mut ev_set_block: EventWriter<SetBlockEvent>,
+
+    actor_query: Query<&Transform, With<NetId>>,
+) {
+    let server = &mut *server;
+    let now = time.seconds_since_startup();
+
+    while let Some((from, message)) = server.socket.receive::<ClientMessage>() {
+        if !server.clients.contains_key(&from) {
+            if !matches!(message, ClientMessage::Hello) {
+                continue;
+            }
+
+            let id = server.next_id;
+            server.next_id += 1;
+
+            let entity = commands
+                .spawn()
+                .insert(Transform::from_translation(**spawn_point))
+                .insert(GlobalTransform::identity())
+                .insert(NetId(id))
+                .insert(Falls)
+                .insert(AabbCollider::new(Vec3A::new(0.4, 1.8, 0.4)))
+                .insert(Velocity(Vec3::ZERO))
+                .insert(Submerged::default())
+                .insert(InputDriven)
+                .id();
+
+            info!("{} joined as actor {}", from, id);
+
+            server.clients.insert(from, ConnectedClient {
+                id,
+                entity,
+                last_heard: now,
+                inputs: VecDeque::new(),
+                last_input: 0,
+                input_budget: 0.0,
+                next_edit: 0,
+                edit_ack: server.edit_log_start + server.edit_log.len() as u32,
+                pending_chunks: loaded_chunks.keys().copied().collect(),
+            });
+        }
+
+        let client = server.clients.get_mut(&from).unwrap();
+        client.last_heard = now;
+
+        match message {
+            ClientMessage::Hello => {
+                // Sent again until we answer, so this might be a repeat of one whose Welcome got lost.
+                let welcome = ServerMessage::Welcome {
+                    actor: client.id,
+                    seed: **seed,
+                    config: *config,
+                    spawn: **spawn_point,
+                    chunks: loaded_chunks.keys().copied().collect(),
+                    first_edit: client.edit_ack,
+                };
+                server.socket.send(from, &welcome);
+            }
+            ClientMessage::Inputs(frames) => {
+                for frame in frames {
+                    // NaN would get through the dt clamp and break physics for everyone
+                    if !(frame.dt.is_finite() && frame.yaw.is_finite() && frame.intent.stick.is_finite()) {
+                        continue;
+                    }
+
+                    let newest = client.inputs.back().map_or(client.last_input, |frame| frame.tick);
+                    if frame.tick > newest {
+                        client.inputs.push_back(frame);
+                    }
+                }
+            }
+            ClientMessage::Edits { first, edits } => {
+                let opt_position = actor_query.get(client.entity).ok().map(|transform| transform.translation);
+
+                for (sequence, edit) in (first..).zip(edits) {
+                    if sequence != client.next_edit {
+                        continue;
+                    }
+                    client.next_edit += 1;
+
+                    if let Err(reason) = check_edit(&edit, opt_position, &config, &loaded_chunks) {
+                        warn!("Rejected an edit from {}: {}", from, reason);
+
+                        // Sending back what's really there puts their copy of the block right again.
+                        if let SetBlockShape::Block(location) = edit.shape {
+                            if let Some(block) = loaded_chunks.get_block(location) {
+                                ev_set_block.send(SetBlockEvent { shape: edit.shape, block });
+                            }
+                        }
+                        continue;
+                    }
+
+                    ev_set_block.send(edit.to_event());
+                }
+            }
+            ClientMessage::AckChunk(index) => {
+                client.pending_chunks.retain(|pending| *pending != index);
+            }
+            ClientMessage::AckEdits(next) => {
+                client.edit_ack = client.edit_ack.max(next);
+            }
+            ClientMessage::Goodbye => {
+                info!("{} left", from);
+                commands.entity(client.entity).despawn();
+                server.clients.remove(&from);
+            }
+        }
+    }
+
+    // Drop anyone we haven't heard from in a while
+    let timed_out: Vec<SocketAddr> = server.clients.iter()
+        .filter(|(_, client)| now - client.last_heard > TIMEOUT)
+        .map(|(address, _)| *address)
+        .collect();
+
+    for address in timed_out {
+        info!("{} timed out", address);
+        if let Some(client) = server.clients.remove(&address) {
+            commands.entity(client.entity).despawn();
+        }
+    }
+}
+
+/// Steps each client's player once for every input frame that's come in, with that frame's dt, the same way the client predicted it.
+/// They're InputDriven, so they don't move on ticks where nothing came in.
+fn move_remote_players (
+    mut server: ResMut<NetServer>,
+    time: Res<Time>,
+    gravity: Res<Gravity>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    mut query: Query<(&mut Transform, &mut Velocity, &AabbCollider, &mut Submerged, Option<&Wades>), (With<NetId>, With<InputDriven>)>,
+) {
+    for client in server.clients.values_mut() {
+        let (mut transform, mut velocity, aabb, mut submerged, opt_wades) = match query.get_mut(client.entity) {
+            Ok(actor) => actor,
+            Err(_) => continue,
+        };
+
+        client.input_budget = (client.input_budget + time.delta_seconds()).min(MAX_INPUT_BUDGET);
+
+        while let Some(frame) = client.inputs.pop_front() {
+            let dt = frame.dt.clamp(0.0, MAX_INPUT_DT);
+            client.last_input = frame.tick;
+
+            // They're sending more movement than time has passed, so this frame never happened.
+            if dt > client.input_budget {
+                continue;
+            }
+            client.input_budget -= dt;
+
+            let swimming = submerged.amount > SWIM_THRESHOLD;
+
+            transform.rotation = Quat::from_rotation_y(frame.yaw);
+            walk(&frame.intent, &transform, &mut velocity, swimming, dt);
+            step_gravity(&mut velocity, &transform, Some(aabb), Some(&mut *submerged), **gravity, dt, &loaded_chunks);
+            step_velocity(&mut velocity, &mut transform, Some(aabb), opt_wades, dt, &loaded_chunks);
+        }
+    }
+}
+
+/// Keeps every block edit once the game's running, so clients can be sent whatever they've missed.
+fn log_edits (
+    mut server: ResMut<NetServer>,
+    state: Res<CurrentState<GameState>>,
+
+    mut ev_set_block: EventReader<SetBlockEvent>,
+) {
+    // Map gen happens before anyone connects, and new clients get it as chunk data instead.
+    if state.0 != GameState::Playing {
+        ev_set_block.iter().for_each(drop);
+        return;
+    }
+
+    for ev in ev_set_block.iter() {
+        server.edit_log.push_back(NetEdit::from(ev));
+    }
+
+    // Forget edits every client has seen
+    let oldest_ack = server.clients.values()
+        .map(|client| client.edit_ack)
+        .min()
+        .unwrap_or(server.edit_log_start + server.edit_log.len() as u32);
+
+    while server.edit_log_start < oldest_ack && !server.edit_log.is_empty() {
+        server.edit_log.pop_front();
+        server.edit_log_start += 1;
+    }
+}
+
+fn send_updates (
+    mut server: ResMut<NetServer>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    query: Query<(&NetId, &Transform, &Velocity)>,
+) {
+    let server = &mut *server;
+
+    let actors: Vec<ActorState> = query.iter()
+        .map(|(id, transform, velocity)| ActorState {
+            id: id.0,
+            translation: transform.translation,
+            yaw: transform.rotation.to_euler(EulerRot::YXZ).0,
+            velocity: **velocity,
+        })
+        .collect();
+
+    for (address, client) in server.clients.iter_mut() {
+        server.socket.send(*address, &ServerMessage::Snapshot(Snapshot {
+            input_ack: client.last_input,
+            edit_ack: client.next_edit,
+            actors: actors.clone(),
+        }));
+
+        // Edits they haven't acknowledged yet, oldest first
+        let skip = client.edit_ack.saturating_sub(server.edit_log_start) as usize;
+        let unacked: Vec<NetEdit> = server.edit_log.iter()
+            .skip(skip)
+            .take(MAX_EDITS_PER_PACKET * EDIT_PACKETS_PER_TICK)
+            .copied()
+            .collect();
+
+        for (packet, edits) in unacked.chunks(MAX_EDITS_PER_PACKET).enumerate() {
+            let first = server.edit_log_start + (skip + packet * MAX_EDITS_PER_PACKET) as u32;
+            server.socket.send(*address, &ServerMessage::Edits { first, edits: edits.to_vec() });
+        }
+
+        // Chunks go round in a queue until they're acknowledged.
+        for _ in 0..CHUNKS_PER_TICK.min(client.pending_chunks.len()) {
+            let index = match client.pending_chunks.pop_front() {
+                Some(index) => index,
+                None => break,
+            };
+
+            if let Some(chunk) = loaded_chunks.get(&index) {
+                for message in chunk_messages(index, chunk.blocks()) {
+                    server.socket.send(*address, &message);
+                }
+                client.pending_chunks.push_back(index);
+            }
+        }
+    }
+}
+
+// Helper functions
+/// Whether a client's allowed to make an edit. They only get to change single blocks that are loaded, editable and within reach of their player.
+fn check_edit(edit: &NetEdit, opt_position: Option<Vec3>, config: &WorldConfig, loaded_chunks: &LoadedChunks) -> Result<(), String> {
+    let location = match edit.shape {
+        SetBlockShape::Block(location) => location,
+        shape => return Err(format!("{:?} isn't a single block", shape)),
+    };
+
+    if !config.is_editable(location) {
+        return Err(format!("{} is outside the world's height limits", location));
+    }
+
+    if loaded_chunks.get_block(location).is_none() {
+        return Err(format!("{} isn't loaded", location));
+    }
+
+    let position = opt_position.ok_or_else(|| "they don't have a player yet".to_string())?;
+    if position.distance(location.as_vec3()) > REACH + EDIT_REACH_SLACK {
+        return Err(format!("{} is out of reach", location));
+    }
+
+    Ok(())
+}
+
+// Resources
+pub struct NetServer {
+    socket: NetSocket,
+    clients: HashMap<SocketAddr, ConnectedClient>,
+    next_id: u32,
+    // Every edit since the oldest one a client hasn't acknowledged. The front one has sequence number edit_log_start.
+    edit_log: VecDeque<NetEdit>,
+    edit_log_start: u32,
+}
+impl NetServer {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let socket = NetSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))?;
+
+        Ok(Self {
+            socket,
+            clients: HashMap::default(),
+            next_id: 0,
+            edit_log: VecDeque::new(),
+            edit_log_start: 0,
+        })
+    }
+}
+
+// Data
+struct ConnectedClient {
+    id: u32,
+    // Their player actor
+    entity: Entity,
+    last_heard: f64,
+    inputs: VecDeque<InputFrame>,
+    last_input: u32,
+    // Seconds of movement they can still simulate, see MAX_INPUT_BUDGET
+    input_budget: f32,
+    // The next edit we're expecting from them
+    next_edit: u32,
+    // The next edit of ours they're expecting
+    edit_ack: u32,
+    pending_chunks: VecDeque<IVec3>,
+}
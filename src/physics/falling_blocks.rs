@@ -0,0 +1,165 @@
+use bevy::{prelude::*, ecs::event::{Events, ManualEventReader}};
+
+use crate::{map::{LoadedChunks, SetBlockEvent, SetBlockShape, Block, BlockType}, physics::{AabbCollider, Velocity, Falls}, player::Health};
+
+
+// Consts
+// Slightly smaller than a block so falling blocks don't snag on the walls of the shaft they fall down.
+const FALLING_BLOCK_SIZE: f32 = 0.98;
+const FALLING_BLOCK_DAMAGE: f32 = 6.0;
+
+// Plugin
+#[derive(Default)]
+pub struct FallingBlocksPlugin;
+impl Plugin for FallingBlocksPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .init_resource::<FallingBlockAssets>();
+
+    }
+}
+
+// Systems
+/// Turns blocks that have lost their support into falling block entities.
+pub fn detach_unsupported_blocks (
+    mut commands: Commands,
+
+    mut reader: Local<ManualEventReader<SetBlockEvent>>,
+    mut ev_set_block: ResMut<Events<SetBlockEvent>>,
+
+    loaded_chunks: Res<LoadedChunks>,
+    assets: Res<FallingBlockAssets>,
+) {
+    let mut need_check = Vec::<IVec3>::new();
+
+    for ev in reader.iter(&ev_set_block) {
+        match ev.shape {
+            SetBlockShape::Block(location) => {
+                // Whatever was placed might be unsupported itself, and whatever was above it might have just lost its support.
+                need_check.push(location);
+                need_check.push(location + IVec3::Y);
+            }
+            SetBlockShape::Range(..) => {
+                // Same again, but only the bottom layer can be unsupported. Everything above sits on it, and follows it down once it falls.
+                let (min, max) = ev.shape.bounds();
+                for x in min.x..=max.x {
+                    for z in min.z..=max.z {
+                        need_check.push(IVec3::new(x, min.y, z));
+                        need_check.push(IVec3::new(x, max.y + 1, z));
+                    }
+                }
+            }
+            SetBlockShape::Chunk(_) => {}
+        }
+    }
+
+    for location in need_check {
+        let block = match loaded_chunks.get_block(location) {
+            Some(block) if block.block_type().falls_when_unsupported() => block,
+            _ => continue,
+        };
+
+        let supported = loaded_chunks.get_block(location - IVec3::Y)
+            .map_or(true, |below| below.collidable());
+
+        if supported {
+            continue;
+        }
+
+        ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Block(location), block: Block::new(BlockType::Air) });
+        spawn_falling_block(&mut commands, &assets, location, block);
+    }
+}
+
+/// Puts falling blocks back into the world once they hit the ground, and hurts anything they land on.
+pub fn land_falling_blocks (
+    mut commands: Commands,
+
+    falling_query: Query<(Entity, &FallingBlock, &Velocity, &Transform, &AabbCollider)>,
+    mut actor_query: Query<(&Transform, &AabbCollider, &mut Health)>,
+
+    mut ev_set_block: EventWriter<SetBlockEvent>,
+    loaded_chunks: Res<LoadedChunks>,
+) {
+    for (entity, falling_block, velocity, transform, aabb) in falling_query.iter() {
+        let falling_aabb = AabbCollider::add_location(transform.translation, aabb);
+
+        let mut hit_actor = false;
+        for (actor_transform, actor_aabb, mut health) in actor_query.iter_mut() {
+            if falling_aabb.compare_simple(AabbCollider::add_location(actor_transform.translation, actor_aabb)) {
+                health.current = (health.current - FALLING_BLOCK_DAMAGE).max(0.0);
+                hit_actor = true;
+            }
+        }
+
+        // Landing on someone breaks the block.
+        if hit_actor {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // apply_velocity zeroes our velocity when we hit the floor.
+        if velocity.y != 0.0 {
+            continue;
+        }
+
+        let mut location = transform.translation.round().as_ivec3();
+        let grounded = loaded_chunks.get_block(location - IVec3::Y)
+            .map_or(true, |below| below.collidable());
+
+        if !grounded {
+            continue;
+        }
+
+        // Don't overwrite anything that got into our spot while we were falling.
+        while loaded_chunks.get_block(location).map_or(false, |block| block.collidable()) {
+            location += IVec3::Y;
+        }
+
+        ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Block(location), block: **falling_block });
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Helper functions
+/// Spawns a falling block entity. The caller is responsible for removing the block from the world.
+pub fn spawn_falling_block(commands: &mut Commands, assets: &FallingBlockAssets, location: IVec3, block: Block) {
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: assets.mesh.clone(),
+            material: assets.material.clone(),
+            transform: Transform::from_translation(location.as_vec3()),
+            ..default()
+        })
+        .insert(FallingBlock(block))
+        .insert(Falls)
+        .insert(Velocity(Vec3::ZERO))
+        .insert(AabbCollider::new(Vec3::splat(FALLING_BLOCK_SIZE).into()));
+}
+
+// Components
+/// A block that's currently falling. Holds what gets put back into the world when it lands.
+#[derive(Component, Deref, DerefMut, Clone, Copy, Debug)]
+pub struct FallingBlock(pub Block);
+
+// Resources
+pub struct FallingBlockAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+impl FromWorld for FallingBlockAssets {
+    fn from_world(world: &mut World) -> Self {
+        // Without a renderer (the server) falling blocks just get default handles.
+        let mesh = world.get_resource_mut::<Assets<Mesh>>()
+            .map(|mut meshes| meshes.add(Mesh::from(shape::Cube { size: FALLING_BLOCK_SIZE })))
+            .unwrap_or_default();
+
+        let mut material = StandardMaterial::from(Color::rgb(0.0, 0.0, 0.0));
+        material.perceptual_roughness = 0.9;
+        let material = world.get_resource_mut::<Assets<StandardMaterial>>()
+            .map(|mut materials| materials.add(material))
+            .unwrap_or_default();
+
+        Self { mesh, material }
+    }
+}
@@ -1,15 +1,25 @@
 use bevy::{prelude::*, math::Vec3A, asset::LoadContext};
+#[cfg(feature = "debug")]
 use bevy_inspector_egui::Inspectable;
 
 use crate::map::{LoadedChunks, BlockType, Block};
 
+// Consts
+// Below this much submersion an actor counts as standing in fluid rather than swimming.
+pub const SWIM_THRESHOLD: f32 = 0.4;
+// Extra horizontal drag per second on a fully submerged actor with a Wades of 0.0
+const WADE_DRAG: f32 = 8.0;
+
 // Plugin
 #[derive(Default)]
 pub struct PhysicsPlugin;
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
         app
-         .init_resource::<Gravity>();
+         .init_resource::<Gravity>()
+         .init_resource::<StepTime>()
+
+         .add_system_to_stage(CoreStage::PreUpdate, update_step_time);
 
     }
 }
@@ -23,59 +33,136 @@ impl Default for Gravity {
     }
 }
 
+/// How far the simulation moves forward this frame. Normally the frame time, but replays set it to what was recorded so they play back the same.
+#[derive(Default)]
+pub struct StepTime {
+    delta: f32,
+}
+impl StepTime {
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta
+    }
+
+    pub fn set_delta_seconds(&mut self, delta: f32) {
+        self.delta = delta;
+    }
+}
+
 // Systems
+pub fn update_step_time (
+    time: Res<Time>,
+    mut step_time: ResMut<StepTime>,
+) {
+    step_time.set_delta_seconds(time.delta_seconds());
+}
+
 pub fn apply_velocity (
-    mut velocity_query: Query<(&mut Velocity, &mut Transform, Option<&AabbCollider>)>,
+    mut velocity_query: Query<(&mut Velocity, &mut Transform, Option<&AabbCollider>, Option<&Wades>), Without<InputDriven>>,
 
-    time: Res<Time>,
+    time: Res<StepTime>,
     loaded_chunks: Res<LoadedChunks>,
 ) {
-    for (mut velocity, mut transform, opt_aabb) in velocity_query.iter_mut() {
-        // TODO: Check whether to use air resistance or ground resistance and use it.
-        // TODO: Check if the direction we're moving has anything collidable and cancel velocity if it does
-        if let Some(aabb) = opt_aabb {
-            let velocities = velocity.to_array();
-            // actually i'm not sure if we need to map these? it might be fine to keep them as references
-            let mut velocities: Vec<(usize, f32)> = velocities.iter().enumerate().map(|(axis, mag)| (axis, *mag)).collect();
-            velocities.sort_by(|(_axis0, mag0), (_axis1, mag1)| mag0.abs().partial_cmp(&mag1.abs()).unwrap());
-            
-            let half_extents = aabb.get_half_extents();
-            
-            for (axis, mag) in velocities {
-                let modified_aabb = AabbCollider::add_location(transform.translation, aabb); //+ **velocity
-                let (normal, collision) = loaded_chunks.aabb_collides_simple(axis, **velocity, modified_aabb);
-                //println!("axis: {}, normal: {}", axis, normal);
-                println!("location: {}", transform.translation);
-                println!("aabb: {:?}", modified_aabb);
-
-                if normal > 0.0 && mag > 0.0 {
-                    velocity[axis] = 0.0;
-                    transform.translation[axis] = collision[axis] - 0.5 - half_extents[axis];
-                }
-                else if normal < 0.0 && mag < 0.0 {
-                    velocity[axis] = 0.0;
-                    transform.translation[axis] = collision[axis] + 0.5 + half_extents[axis];
-                }
-                else {
-                    transform.translation[axis] += velocity[axis] * time.delta_seconds();
-                }
-            }
-        }
-        else {
-            transform.translation += **velocity * time.delta_seconds();
-        }
-        
+    for (mut velocity, mut transform, opt_aabb, opt_wades) in velocity_query.iter_mut() {
+        step_velocity(&mut velocity, &mut transform, opt_aabb, opt_wades, time.delta_seconds(), &loaded_chunks);
     }
 }
 
 pub fn apply_gravity (
-    mut velocity_query: Query<(&mut Velocity), With<Falls>>,
+    mut velocity_query: Query<(&mut Velocity, &Transform, Option<&AabbCollider>, Option<&mut Submerged>), (With<Falls>, Without<InputDriven>)>,
 
     gravity: Res<Gravity>,
-    time: Res<Time>,
+    time: Res<StepTime>,
+    loaded_chunks: Res<LoadedChunks>,
 ) {
-    for mut velocity in  velocity_query.iter_mut() {
-        **velocity += **gravity * time.delta_seconds();
+    for (mut velocity, transform, opt_aabb, opt_submerged) in  velocity_query.iter_mut() {
+        step_gravity(&mut velocity, transform, opt_aabb, opt_submerged.map(|submerged| submerged.into_inner()), **gravity, time.delta_seconds(), &loaded_chunks);
+    }
+}
+
+// Helper functions
+/// Moves an actor by its velocity for `dt` seconds, stopping it at anything collidable. What apply_velocity does for each actor.
+pub fn step_velocity(velocity: &mut Velocity, transform: &mut Transform, opt_aabb: Option<&AabbCollider>, opt_wades: Option<&Wades>, dt: f32, loaded_chunks: &LoadedChunks) {
+    // TODO: Check whether to use air resistance or ground resistance and use it.
+    // TODO: Check if the direction we're moving has anything collidable and cancel velocity if it does
+    if let Some(aabb) = opt_aabb {
+        let (submersion, opt_fluid) = loaded_chunks.aabb_fluid_overlap(AabbCollider::add_location(transform.translation, aabb));
+
+        if let Some(fluid) = opt_fluid {
+            let drag = (1.0 - fluid_drag(fluid) * submersion * dt).max(0.0);
+            **velocity *= drag;
+
+            if let Some(wades) = opt_wades {
+                let slowdown = (1.0 - WADE_DRAG * (1.0 - **wades) * submersion * dt).max(0.0);
+                velocity.x *= slowdown;
+                velocity.z *= slowdown;
+            }
+        }
+
+        let velocities = velocity.to_array();
+        // actually i'm not sure if we need to map these? it might be fine to keep them as references
+        let mut velocities: Vec<(usize, f32)> = velocities.iter().enumerate().map(|(axis, mag)| (axis, *mag)).collect();
+        velocities.sort_by(|(_axis0, mag0), (_axis1, mag1)| mag0.abs().partial_cmp(&mag1.abs()).unwrap());
+        
+        let half_extents = aabb.get_half_extents();
+        
+        for (axis, mag) in velocities {
+            let modified_aabb = AabbCollider::add_location(transform.translation, aabb); //+ **velocity
+            let (normal, collision) = loaded_chunks.aabb_collides_simple(axis, **velocity, modified_aabb);
+
+            if normal > 0.0 && mag > 0.0 {
+                velocity[axis] = 0.0;
+                transform.translation[axis] = collision[axis] - 0.5 - half_extents[axis];
+            }
+            else if normal < 0.0 && mag < 0.0 {
+                velocity[axis] = 0.0;
+                transform.translation[axis] = collision[axis] + 0.5 + half_extents[axis];
+            }
+            else {
+                transform.translation[axis] += velocity[axis] * dt;
+            }
+        }
+    }
+    else {
+        transform.translation += **velocity * dt;
+    }
+}
+
+/// Pulls an actor down for `dt` seconds, less whatever fluid it's in holds it up. What apply_gravity does for each actor.
+pub fn step_gravity(velocity: &mut Velocity, transform: &Transform, opt_aabb: Option<&AabbCollider>, opt_submerged: Option<&mut Submerged>, gravity: Vec3, dt: f32, loaded_chunks: &LoadedChunks) {
+    let (submersion, opt_fluid) = match opt_aabb {
+        Some(aabb) => loaded_chunks.aabb_fluid_overlap(AabbCollider::add_location(transform.translation, aabb)),
+        None => (0.0, None),
+    };
+
+    if let Some(submerged) = opt_submerged {
+        submerged.amount = submersion;
+        submerged.fluid = opt_fluid;
+    }
+
+    // Buoyancy pushes against gravity in proportion to how much of us is under.
+    let buoyancy = match opt_fluid {
+        Some(fluid) => fluid_buoyancy(fluid) * submersion,
+        None => 0.0,
+    };
+
+    **velocity += gravity * (1.0 - buoyancy) * dt;
+}
+
+/// How strongly a fluid pushes up, relative to gravity, when fully submerged.
+fn fluid_buoyancy(fluid: BlockType) -> f32 {
+    match fluid {
+        BlockType::Water => 1.05,
+        BlockType::Lava => 0.9,
+        _ => 0.0,
+    }
+}
+
+/// The fraction of velocity a fluid removes per second when fully submerged.
+fn fluid_drag(fluid: BlockType) -> f32 {
+    match fluid {
+        BlockType::Water => 2.0,
+        BlockType::Lava => 4.0,
+        _ => 0.0,
     }
 }
 
@@ -207,12 +294,29 @@ impl AabbCollider {
     }
 }
 
-#[derive(Copy, Clone, Component, Deref, DerefMut, Debug, Reflect, Inspectable)]
+#[derive(Copy, Clone, Component, Deref, DerefMut, Debug, Reflect)]
+#[cfg_attr(feature = "debug", derive(Inspectable))]
 pub struct Velocity (pub Vec3);
 
 #[derive(Component)]
 pub struct Falls;
 
+/// Moved a step at a time as its owner's input comes in, like a client's player on the server, instead of by apply_gravity and apply_velocity.
+#[derive(Component)]
+pub struct InputDriven;
+
+/// How much of an actor is in fluid, from 0.0 to 1.0. Kept up to date by apply_gravity.
+#[derive(Component, Default, Clone, Copy, Debug)]
+pub struct Submerged {
+    pub amount: f32,
+    pub fluid: Option<BlockType>,
+}
+
+/// How easily an actor moves sideways through fluid, from 0.0 (heaviest extra drag) to 1.0 (no extra drag).
+/// Actors without this aren't slowed beyond fluid drag.
+#[derive(Component, Deref, DerefMut, Clone, Copy, Debug)]
+pub struct Wades(pub f32);
+
 
 #[derive(Component)]
 pub struct AirResistance(Vec3);
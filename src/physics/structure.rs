@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, ecs::event::{Events, ManualEventReader}, utils::{HashMap, HashSet}};
+
+use crate::{map::{LoadedChunks, SetBlockEvent, SetBlockShape, Block, BlockType, WithinBoxIterator, BLOCK_SIDES}, falling_blocks::{FallingBlockAssets, spawn_falling_block}};
+
+
+// Consts
+// How far around a mined block we look when recalculating support.
+// Blocks on the edge of this region are assumed to be supported, so it needs to be comfortably bigger than any span limit.
+const STRESS_RADIUS: i32 = 8;
+
+// Systems
+/// Recalculates support around mined blocks, and drops anything left hanging past its span limit.
+pub fn collapse_unsupported (
+    mut commands: Commands,
+
+    mut reader: Local<ManualEventReader<SetBlockEvent>>,
+    mut ev_set_block: ResMut<Events<SetBlockEvent>>,
+
+    loaded_chunks: Res<LoadedChunks>,
+    assets: Res<FallingBlockAssets>,
+) {
+    let mut centers = Vec::<IVec3>::new();
+
+    for ev in reader.iter(&ev_set_block) {
+        if matches!(ev.shape, SetBlockShape::Chunk(_)) || ev.block.block_type() != BlockType::Air {
+            continue;
+        }
+
+        let (min, max) = ev.shape.bounds();
+        for location in WithinBoxIterator::new(min, max) {
+            // Nearby edits share a region. Checking each of them would be the same work over again.
+            let covered = centers.iter().any(|center| (*center - location).abs().max_element() <= STRESS_RADIUS / 2);
+            if !covered {
+                centers.push(location);
+            }
+        }
+    }
+
+    let mut collapsed = HashSet::<IVec3>::default();
+
+    for center in centers {
+        for location in unsupported_blocks(&loaded_chunks, center) {
+            // Sand and the like are already handled by falling_blocks once what's under them goes.
+            let falls_anyway = loaded_chunks.get_block(location)
+                .map_or(true, |block| block.block_type().falls_when_unsupported());
+
+            if falls_anyway || !collapsed.insert(location) {
+                continue;
+            }
+
+            if let Some(block) = loaded_chunks.get_block(location) {
+                spawn_falling_block(&mut commands, &assets, location, block);
+            }
+        }
+    }
+
+    for location in collapsed {
+        ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Block(location), block: Block::new(BlockType::Air) });
+    }
+}
+
+// Helper functions
+/// Returns every solid block within STRESS_RADIUS of center that can't hold itself up.
+///
+/// Stress starts at 0 on anchors and spreads through solid blocks.
+/// Resting on top of a block is free, anything else (sideways, hanging underneath) costs 1.
+/// A block that's over its span limit can't hold anything else up.
+pub fn unsupported_blocks(loaded_chunks: &LoadedChunks, center: IVec3) -> Vec<IVec3> {
+    let min = center - IVec3::splat(STRESS_RADIUS);
+    let max = center + IVec3::splat(STRESS_RADIUS);
+
+    let in_region = |position: IVec3| {
+        position.cmpge(min).all() && position.cmple(max).all()
+    };
+
+    let solid = |position: IVec3| {
+        loaded_chunks.get_block(position).filter(|block| block.collidable())
+    };
+
+    let mut stress = HashMap::<IVec3, u8>::default();
+    let mut queue = VecDeque::<IVec3>::new();
+
+    for position in WithinBoxIterator::new(min, max) {
+        let block = match solid(position) {
+            Some(block) => block,
+            None => continue,
+        };
+
+        let on_edge = position.cmpeq(min).any() || position.cmpeq(max).any();
+        // Unloaded chunks count as solid everywhere else, so they count as solid ground here too.
+        let on_unloaded = loaded_chunks.get_block(position - IVec3::Y).is_none();
+
+        if block.block_type().is_anchor() || on_edge || on_unloaded {
+            stress.insert(position, 0);
+            queue.push_back(position);
+        }
+    }
+
+    // 0-1 BFS, so every block ends up with the lowest stress it can get.
+    while let Some(position) = queue.pop_front() {
+        let current = stress[&position];
+
+        let block = match solid(position) {
+            Some(block) => block,
+            None => continue,
+        };
+
+        if current > block.block_type().max_span() {
+            continue;
+        }
+
+        for side in BLOCK_SIDES {
+            let next = position + side;
+            if !in_region(next) || solid(next).is_none() {
+                continue;
+            }
+
+            let cost = if side == IVec3::Y {0} else {1};
+            let next_stress = current.saturating_add(cost);
+
+            if stress.get(&next).map_or(true, |existing| next_stress < *existing) {
+                stress.insert(next, next_stress);
+
+                if cost == 0 {
+                    queue.push_front(next);
+                }
+                else {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    WithinBoxIterator::new(min, max)
+        .filter(|position| {
+            match solid(*position) {
+                Some(block) => stress.get(position).map_or(true, |stress| *stress > block.block_type().max_span()),
+                None => false,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::Chunk, storage::ChunkStorage};
+
+    // Far enough from chunk borders that the whole stress region is loaded, with the region's floor at y = 0.
+    const CENTER: IVec3 = IVec3::new(8, 8, 8);
+
+    // Loads every chunk the stress region around CENTER touches as air, then places the given blocks.
+    fn world(blocks: &[(IVec3, BlockType)]) -> LoadedChunks {
+        let mut loaded_chunks = LoadedChunks::default();
+
+        for index in WithinBoxIterator::new(IVec3::splat(-1), IVec3::splat(1)) {
+            let chunk = Chunk::new(ChunkStorage::filled(Block::new(BlockType::Air)), Entity::from_raw(0), Entity::from_raw(1));
+            loaded_chunks.insert(index, chunk);
+        }
+
+        for (position, block_type) in blocks {
+            assert!(loaded_chunks.set_block(*position, Block::new(*block_type)));
+        }
+
+        loaded_chunks
+    }
+
+    fn column(x: i32, z: i32, top: i32, block_type: BlockType) -> Vec<(IVec3, BlockType)> {
+        (0..=top).map(|y| (IVec3::new(x, y, z), block_type)).collect()
+    }
+
+    fn sorted(positions: Vec<IVec3>) -> Vec<[i32; 3]> {
+        let mut positions: Vec<[i32; 3]> = positions.into_iter().map(|position| position.to_array()).collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    #[test]
+    fn stone_overhang_collapses_past_max_span() {
+        // A pillar with 8 blocks of stone sticking out from its top. Stone spans 6.
+        let mut blocks = column(2, 8, 4, BlockType::Stone);
+        blocks.extend((3..=10).map(|x| (IVec3::new(x, 4, 8), BlockType::Stone)));
+
+        let unsupported = unsupported_blocks(&world(&blocks), CENTER);
+
+        assert_eq!(sorted(unsupported), vec![[9, 4, 8], [10, 4, 8]]);
+    }
+
+    #[test]
+    fn dirt_roof_over_wide_room_collapses() {
+        // Stone walls with 5 blocks of room between them. Dirt spans 2, so the middle of the roof is 3 from either wall.
+        let mut blocks = column(3, 8, 3, BlockType::Stone);
+        blocks.extend(column(9, 8, 3, BlockType::Stone));
+        blocks.extend((3..=9).map(|x| (IVec3::new(x, 4, 8), BlockType::Dirt)));
+
+        let unsupported = unsupported_blocks(&world(&blocks), CENTER);
+
+        assert_eq!(sorted(unsupported), vec![[6, 4, 8]]);
+    }
+
+    #[test]
+    fn infinium_anchor_holds() {
+        // Floating in the air, with as much stone hanging off it as stone can span.
+        let anchor = IVec3::new(8, 8, 8);
+        let mut blocks = vec![(anchor, BlockType::Infinium)];
+        blocks.extend((9..=14).map(|x| (IVec3::new(x, 8, 8), BlockType::Stone)));
+
+        let mut loaded_chunks = world(&blocks);
+        assert!(unsupported_blocks(&loaded_chunks, CENTER).is_empty());
+
+        // The same thing without the anchor has nothing holding it up at all.
+        loaded_chunks.set_block(anchor, Block::new(BlockType::Stone));
+        let unsupported = unsupported_blocks(&loaded_chunks, CENTER);
+
+        assert_eq!(unsupported.len(), blocks.len());
+    }
+}
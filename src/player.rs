@@ -1,30 +1,174 @@
-use bevy::{prelude::*, app::AppExit};
+use bevy::prelude::*;
+use iyes_loopless::state::NextState;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{GameState, actions::Action, items::Inventory, camera::CameraController, map::{LoadedChunks, RaycastHit, SetBlockEvent, SetBlockShape, Block, BlockType}, setup::EYE_HEIGHT, keybinds::{Keybinds, BindInputs}, rebind_menu::RebindMenu, spectator::Spectating, physics::StepTime, replay::Playback, console::Console};
+
+// Consts
+// Breath regained per second while our head is out of fluid.
+const BREATH_RECOVERY: f32 = 4.0;
+// Damage per second once we're out of breath.
+const DROWN_DAMAGE: f32 = 2.0;
+
+// How far away we can reach blocks from
+pub const REACH: f32 = 5.0;
 
 // Components
 #[derive(Component)]
 pub struct Player;
 
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+impl Health {
+    pub fn new (max: f32) -> Self {
+        Self {current: max, max}
+    }
+}
+
+/// Seconds of air left. Drains while our head is in fluid.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Breath {
+    pub current: f32,
+    pub max: f32,
+}
+impl Breath {
+    pub fn new (max: f32) -> Self {
+        Self {current: max, max}
+    }
+}
+
 // Systems
 pub fn meta_input (
+    mut commands: Commands,
     mut windows: ResMut<Windows>,
-    btn: Res<Input<MouseButton>>,
-    key: Res<Input<KeyCode>>,
-
-    mut ev_exit: EventWriter<AppExit>,
+    inputs: BindInputs,
+    keybinds: Res<Keybinds>,
+    rebind_menu: Res<RebindMenu>,
+    console: Res<Console>,
 ) {
+    // The rebind menu and the console need the cursor, and get first dibs on every key.
+    if rebind_menu.open || console.open {
+        return;
+    }
+
     let window = windows.get_primary_mut().unwrap();
 
-    if btn.just_pressed(MouseButton::Left) {
+    if inputs.just_pressed(&keybinds.lock_cursor) {
         window.set_cursor_lock_mode(true);
         window.set_cursor_visibility(false);
     }
 
-    if key.just_pressed(KeyCode::Tab) {
+    if inputs.just_pressed(&keybinds.unlock_cursor) {
         window.set_cursor_lock_mode(false);
         window.set_cursor_visibility(true);
     }
 
-    if key.just_pressed(KeyCode::Escape) {
-        ev_exit.send(AppExit);
+    if inputs.just_pressed(&keybinds.quit) {
+        commands.insert_resource(NextState(GameState::Paused));
     }
-}
\ No newline at end of file
+}
+
+/// Finds the block the player's looking at. Runs after the camera has been placed.
+pub fn update_look_target (
+    mut look_target: ResMut<LookTarget>,
+
+    camera_query: Query<(&Transform, &Parent), With<CameraController>>,
+    player_query: Query<&Transform, (With<Player>, Without<CameraController>)>,
+
+    loaded_chunks: Res<LoadedChunks>,
+) {
+    **look_target = None;
+
+    for (camera_transform, parent) in camera_query.iter() {
+        let player_transform = match player_query.get(**parent) {
+            Ok(transform) => transform,
+            Err(_) => continue,
+        };
+
+        // GlobalTransform is a frame behind here, so work it out from the camera's offset.
+        let eye = player_transform.translation + player_transform.rotation * camera_transform.translation;
+        let forward = player_transform.rotation * camera_transform.rotation * -Vec3::Z;
+
+        **look_target = loaded_chunks.raycast(eye, forward, REACH)
+            // Unloaded chunks count as hits for raycasts, but there's nothing there to mine.
+            .filter(|hit| loaded_chunks.get_block(hit.position).is_some());
+    }
+}
+
+/// Damages whatever block the player's looking at while Mine is held, breaking it into their inventory once it's taken enough.
+pub fn mine (
+    time: Res<StepTime>,
+    windows: Res<Windows>,
+    look_target: Res<LookTarget>,
+    playback: Option<Res<Playback>>,
+    mut loaded_chunks: ResMut<LoadedChunks>,
+
+    mut query: Query<(&ActionState<Action>, &mut Inventory), With<Player>>,
+
+    mut ev_set_block: EventWriter<SetBlockEvent>,
+) {
+    // Clicking to grab the cursor shouldn't start chipping away at things. Replays mine whether or not we've grabbed it.
+    if !windows.get_primary().map_or(false, |window| window.cursor_locked()) && playback.is_none() {
+        return;
+    }
+
+    let target = match **look_target {
+        Some(hit) => hit.position,
+        None => return,
+    };
+
+    for (action_state, mut inventory) in query.iter_mut() {
+        if !action_state.pressed(Action::Mine) {
+            continue;
+        }
+
+        let mut block = match loaded_chunks.get_block(target) {
+            Some(block) => block,
+            None => continue,
+        };
+
+        // Damage doesn't change how the chunk looks, so this skips SetBlockEvent to avoid a remesh every frame.
+        block.set_damage(block.damage() + time.delta_seconds());
+        loaded_chunks.set_block(target, block);
+
+        if block.damage() >= block.block_type().hardness() {
+            let block_type = block.block_type();
+
+            if inventory.add_block(block_type) {
+                ev_set_block.send(SetBlockEvent { shape: SetBlockShape::Block(target), block: Block::new(BlockType::Air) });
+            }
+        }
+    }
+}
+
+pub fn breathe (
+    mut query: Query<(&Transform, &mut Breath, &mut Health), Without<Spectating>>,
+
+    time: Res<StepTime>,
+    loaded_chunks: Res<LoadedChunks>,
+) {
+    for (transform, mut breath, mut health) in query.iter_mut() {
+        let eye = transform.translation + Vec3::new(0.0, EYE_HEIGHT, 0.0);
+        let head_under = loaded_chunks.get_block(eye.round().as_ivec3())
+            .map_or(false, |block| block.block_type().is_fluid());
+
+        if head_under {
+            breath.current = (breath.current - time.delta_seconds()).max(0.0);
+
+            if breath.current <= 0.0 {
+                health.current = (health.current - DROWN_DAMAGE * time.delta_seconds()).max(0.0);
+            }
+        }
+        else {
+            breath.current = (breath.current + BREATH_RECOVERY * time.delta_seconds()).min(breath.max);
+        }
+    }
+}
+
+// Resources
+/// The block the player's looking at, if it's within reach.
+#[derive(Deref, DerefMut, Default)]
+pub struct LookTarget(Option<RaycastHit>);
\ No newline at end of file
@@ -0,0 +1,342 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use bevy::{prelude::*, app::AppExit};
+use iyes_loopless::prelude::*;
+use leafwing_input_manager::prelude::{ActionState, InputMap};
+use serde::{Serialize, Deserialize};
+
+use crate::{GameState, actions::{Action, AnalogInput}, keybinds::Keybinds, map::{WorldSeed, WorldConfig}, physics::{Velocity, StepTime}, player::Player, save::SavedWorld};
+
+
+// Consts
+// Bumped whenever ReplayFrame or Replay change, so old recordings are refused instead of misread.
+const REPLAY_VERSION: u32 = 1;
+// Bit n of ReplayFrame::actions is RECORDED_ACTIONS[n]. Only add to the end, or old recordings will press the wrong things.
+const RECORDED_ACTIONS: [Action; 8] = [Action::WalkForward, Action::WalkBackward, Action::StrafeLeft, Action::StrafeRight, Action::Jump, Action::Crouch, Action::Sprint, Action::Mine];
+
+// Plugin
+/// Records the player's input to a file with `--record <path>`, or plays one back with `--replay <path>`.
+///
+/// Replays start from a new world with the recorded seed and feed back the recorded input and frame times, so movement and physics come out the same.
+/// Each tick also stores a checksum of everything that moves, and playback warns at the first tick that doesn't match.
+/// Fluids run on their own real time clock, so worlds with flowing fluids can still drift.
+#[derive(Default)]
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .add_enter_system(GameState::MainMenu, start_playback)
+         .add_enter_system(GameState::MainMenu, save_recording)
+
+         .add_system(
+             play_inputs
+                 .run_in_state(GameState::Playing)
+                 .before("input")
+                 .before("physics")
+         )
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .after("physics")
+                 .with_system(record_frame)
+                 .with_system(check_playback)
+                 .into()
+         )
+
+         .add_system_to_stage(CoreStage::Last, save_on_exit);
+    }
+}
+
+// Systems
+/// Starts a new world from the replay's seed, the same way the New World button does.
+fn start_playback (
+    mut commands: Commands,
+    opt_playback: Option<Res<Playback>>,
+) {
+    let playback = match opt_playback {
+        Some(playback) => playback,
+        None => return,
+    };
+
+    // Back at the menu partway through, so the player quit out of it.
+    if playback.next > 0 {
+        commands.remove_resource::<Playback>();
+        return;
+    }
+
+    info!("Playing back {} ticks", playback.replay.frames.len());
+    commands.remove_resource::<ReplayOutcome>();
+    commands.insert_resource(WorldSeed(playback.replay.seed));
+    commands.insert_resource(playback.replay.config);
+    commands.remove_resource::<SavedWorld>();
+    commands.insert_resource(NextState(GameState::StartMapGen));
+}
+
+/// Presses what was pressed on this tick, and puts back its analog input and frame time.
+fn play_inputs (
+    mut commands: Commands,
+    opt_playback: Option<Res<Playback>>,
+    mut analog: ResMut<AnalogInput>,
+    mut step_time: ResMut<StepTime>,
+
+    mut query: Query<(Entity, &mut ActionState<Action>, Option<&InputMap<Action>>), With<Player>>,
+) {
+    let playback = match opt_playback {
+        Some(playback) => playback,
+        None => return,
+    };
+
+    let frame = match playback.replay.frames.get(playback.next) {
+        Some(frame) => *frame,
+        None => return,
+    };
+
+    *analog = frame.analog;
+    step_time.set_delta_seconds(frame.dt);
+
+    for (entity, mut action_state, opt_input_map) in query.iter_mut() {
+        // Without an input map the keyboard can't fight us over the action state. It gets put back when the replay's done.
+        if opt_input_map.is_some() {
+            commands.entity(entity).remove::<InputMap<Action>>();
+        }
+
+        for (bit, action) in RECORDED_ACTIONS.iter().enumerate() {
+            let pressed = frame.actions & (1 << bit) != 0;
+
+            // Only on changes, otherwise every tick would count as a fresh press.
+            if pressed && !action_state.pressed(*action) {
+                action_state.press(*action);
+            }
+            else if !pressed && action_state.pressed(*action) {
+                action_state.release(*action);
+            }
+        }
+    }
+}
+
+fn record_frame (
+    opt_recording: Option<ResMut<Recording>>,
+    seed: Res<WorldSeed>,
+    config: Res<WorldConfig>,
+    opt_saved: Option<Res<SavedWorld>>,
+    analog: Res<AnalogInput>,
+    step_time: Res<StepTime>,
+
+    player_query: Query<&ActionState<Action>, With<Player>>,
+    moving_query: Query<&Transform, With<Velocity>>,
+) {
+    let mut recording = match opt_recording {
+        Some(recording) => recording,
+        None => return,
+    };
+
+    if recording.replay.frames.is_empty() {
+        if opt_saved.is_some() {
+            warn!("Recording a loaded world. Replays always start from a new one, so this won't play back the same");
+        }
+        recording.replay.seed = **seed;
+        recording.replay.config = *config;
+    }
+
+    let actions = match player_query.iter().next() {
+        Some(action_state) => action_bits(action_state),
+        None => 0,
+    };
+
+    recording.replay.frames.push(ReplayFrame {
+        dt: step_time.delta_seconds(),
+        actions,
+        analog: *analog,
+        checksum: checksum(moving_query.iter()),
+    });
+}
+
+/// Compares the tick just played against the recording, and hands control back once it's over.
+fn check_playback (
+    mut commands: Commands,
+    opt_playback: Option<ResMut<Playback>>,
+    keybinds: Res<Keybinds>,
+
+    player_query: Query<Entity, With<Player>>,
+    moving_query: Query<&Transform, With<Velocity>>,
+) {
+    let mut playback = match opt_playback {
+        Some(playback) => playback,
+        None => return,
+    };
+
+    let tick = playback.next;
+    let frame = match playback.replay.frames.get(tick) {
+        Some(frame) => *frame,
+        None => return,
+    };
+
+    if checksum(moving_query.iter()) != frame.checksum && playback.diverged.is_none() {
+        warn!("Replay diverged from the recording at tick {}", tick);
+        playback.diverged = Some(tick);
+    }
+
+    playback.next += 1;
+    if playback.next < playback.replay.frames.len() {
+        return;
+    }
+
+    match playback.diverged {
+        Some(tick) => warn!("Replay finished, diverged from tick {} on", tick),
+        None => info!("Replay finished, matched the recording all the way through"),
+    }
+    commands.insert_resource(ReplayOutcome { diverged: playback.diverged });
+
+    for entity in player_query.iter() {
+        commands.entity(entity).insert(keybinds.input_map());
+    }
+    commands.remove_resource::<Playback>();
+}
+
+/// Writes what's been recorded when the world's left. The next world starts a new recording over the top.
+fn save_recording (
+    opt_recording: Option<ResMut<Recording>>,
+) {
+    if let Some(mut recording) = opt_recording {
+        recording.save();
+        recording.replay.frames.clear();
+    }
+}
+
+fn save_on_exit (
+    mut ev_exit: EventReader<AppExit>,
+    opt_recording: Option<ResMut<Recording>>,
+) {
+    if ev_exit.iter().next().is_none() {
+        return;
+    }
+
+    if let Some(mut recording) = opt_recording {
+        recording.save();
+        recording.replay.frames.clear();
+    }
+}
+
+// Helper functions
+fn action_bits(action_state: &ActionState<Action>) -> u8 {
+    RECORDED_ACTIONS.iter()
+        .enumerate()
+        .filter(|(_, action)| action_state.pressed(**action))
+        .fold(0, |bits, (bit, _)| bits | 1 << bit)
+}
+
+/// Hashes where everything that moves is, to the bit. Doesn't depend on the order they're visited in.
+fn checksum<'a>(transforms: impl Iterator<Item = &'a Transform>) -> u32 {
+    let mut sum = 0u64;
+
+    for transform in transforms {
+        let mut hash = 0u64;
+        for value in transform.translation.to_array().into_iter().chain(transform.rotation.to_array()) {
+            // splitmix64, same as hash_position
+            hash = hash.wrapping_add(value.to_bits() as u64).wrapping_add(0x9E3779B97F4A7C15);
+            hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D049BB133111EB);
+            hash ^= hash >> 31;
+        }
+        sum = sum.wrapping_add(hash);
+    }
+
+    (sum ^ (sum >> 32)) as u32
+}
+
+// Resources
+/// Inserted with `--record <path>`.
+pub struct Recording {
+    pub path: PathBuf,
+    replay: Replay,
+}
+impl Recording {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, replay: Replay::default() }
+    }
+
+    fn save(&self) {
+        if self.replay.frames.is_empty() {
+            return;
+        }
+
+        match bincode::serialize(&self.replay) {
+            Ok(bytes) => {
+                match fs::write(&self.path, bytes) {
+                    Ok(()) => info!("Recorded {} ticks to {}", self.replay.frames.len(), self.path.display()),
+                    Err(error) => warn!("Couldn't write {}: {}", self.path.display(), error),
+                }
+            }
+            Err(error) => {
+                warn!("Couldn't serialize replay: {}", error);
+            }
+        }
+    }
+}
+
+/// Inserted with `--replay <path>`, and removed once it's played through.
+pub struct Playback {
+    replay: Replay,
+    // The tick being played this frame
+    next: usize,
+    // The first tick that didn't match the recording
+    diverged: Option<usize>,
+}
+impl Playback {
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                warn!("Couldn't read {}: {}", path.display(), error);
+                return None;
+            }
+        };
+
+        let replay: Replay = match bincode::deserialize(&bytes) {
+            Ok(replay) => replay,
+            Err(error) => {
+                warn!("Couldn't parse {}: {}", path.display(), error);
+                return None;
+            }
+        };
+
+        if replay.version != REPLAY_VERSION {
+            warn!("{} was recorded with replay version {}, but this is version {}", path.display(), replay.version, REPLAY_VERSION);
+            return None;
+        }
+
+        Some(Self { replay, next: 0, diverged: None })
+    }
+}
+
+/// Left behind when a replay finishes playing.
+#[derive(Clone, Copy, Debug)]
+pub struct ReplayOutcome {
+    // The first tick that didn't match the recording
+    pub diverged: Option<usize>,
+}
+
+// Data
+/// A recording on disk. Bincoded, at 25 bytes a tick.
+#[derive(Serialize, Deserialize)]
+struct Replay {
+    version: u32,
+    seed: u64,
+    config: WorldConfig,
+    frames: Vec<ReplayFrame>,
+}
+impl Default for Replay {
+    fn default() -> Self {
+        Self { version: REPLAY_VERSION, seed: 0, config: WorldConfig::default(), frames: Vec::new() }
+    }
+}
+
+/// Everything the player did on one tick, and where that left things.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct ReplayFrame {
+    dt: f32,
+    // Bitmask of RECORDED_ACTIONS
+    actions: u8,
+    analog: AnalogInput,
+    checksum: u32,
+}
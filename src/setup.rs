@@ -1,35 +1,41 @@
-use bevy::{prelude::*, math::Vec3A};
+use bevy::{prelude::*, math::{Vec3A, const_vec3}};
 use iyes_loopless::state::NextState;
 use leafwing_input_manager::prelude::*;
 
-use crate::{actions::Action, player::Player, GameState, physics::{AabbCollider, Velocity, Falls}};
+use crate::{actions::{Action, Stance, Stamina, MoveIntent}, player::{Player, Health, Breath}, GameState, physics::{AabbCollider, Velocity, Falls, Submerged, Wades}, keybinds::Keybinds, camera::CameraController, map::SpawnPoint, falling_blocks::FallingBlock, items::Inventory};
 
 //use super::{GameState, TextureAssets};
 
 const PLAYER_HEIGHT: f32 = 0.4;
+pub const EYE_HEIGHT: f32 = PLAYER_HEIGHT - (PLAYER_HEIGHT / 4.0);
+const ZOMBIE_SIZE: Vec3 = const_vec3!([0.6, 1.8, 0.6]);
 
 // Systems
+/// Runs on entering GameState::SpawnActors.
 pub fn spawn_actors (
     mut commands: Commands,
+    keybinds: Res<Keybinds>,
+    spawn_point: Res<SpawnPoint>,
 ) {
-    let spawn_pos = Vec3::new(0.0, 0.0, 0.0);
+    let spawn_pos = **spawn_point;
 
     // Player
     commands
         .spawn_bundle(InputManagerBundle::<Action> {
             action_state: ActionState::default(),
-            input_map: InputMap::new([(Action::Jump, KeyCode::Space),
-                                      (Action::Crouch, KeyCode::LControl),
-                                      (Action::StrafeRight, KeyCode::D),
-                                      (Action::StrafeLeft, KeyCode::A),
-                                      (Action::WalkForward, KeyCode::W),
-                                      (Action::WalkBackward, KeyCode::S),
-                                     ])
+            input_map: keybinds.input_map(),
         })
         .insert(Player)
         .insert(Falls)
         .insert(AabbCollider::new(Vec3A::new(0.4, 1.8, 0.4)))
         .insert(Velocity(Vec3::new(0.0, 0.0, 0.0)))
+        .insert(Submerged::default())
+        .insert(Health::new(20.0))
+        .insert(Breath::new(10.0))
+        .insert(Stance::default())
+        .insert(Stamina::new(100.0))
+        .insert(Inventory::default())
+        .insert(MoveIntent::default())
         .insert(Transform {
             translation: spawn_pos,
             ..default()
@@ -43,9 +49,10 @@ pub fn spawn_actors (
         .with_children(|c| {
             c.spawn_bundle(PerspectiveCameraBundle::new_3d())
                 .insert( Transform {
-                    translation: Vec3::new(0.0, PLAYER_HEIGHT - (PLAYER_HEIGHT / 4.0), 0.0),
+                    translation: Vec3::new(0.0, EYE_HEIGHT, 0.0),
                     ..default()
-                });
+                })
+                .insert(CameraController::new(EYE_HEIGHT));
         })
         // light
         .with_children(|c| {
@@ -62,4 +69,59 @@ pub fn spawn_actors (
 
 
     commands.insert_resource(NextState(GameState::Playing));
-}
\ No newline at end of file
+}
+
+/// Gets rid of everything spawned into the world, for going back to the main menu.
+pub fn despawn_actors (
+    mut commands: Commands,
+
+    query: Query<Entity, Or<(With<Player>, With<FallingBlock>, With<Zombie>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Helper functions
+/// Zombies don't do anything yet. They fall and wade through water like the player does, and that's it.
+pub fn spawn_zombie(commands: &mut Commands, assets: &ZombieAssets, position: Vec3) -> Entity {
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: assets.mesh.clone(),
+            material: assets.material.clone(),
+            transform: Transform::from_translation(position),
+            ..default()
+        })
+        .insert(Zombie)
+        .insert(Falls)
+        .insert(AabbCollider::new(Vec3A::from(ZOMBIE_SIZE)))
+        .insert(Velocity(Vec3::ZERO))
+        .insert(Submerged::default())
+        .insert(Wades(0.5))
+        .insert(Health::new(20.0))
+        .id()
+}
+
+// Components
+#[derive(Component)]
+pub struct Zombie;
+
+// Resources
+pub struct ZombieAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+impl FromWorld for ZombieAssets {
+    fn from_world(world: &mut World) -> Self {
+        // Without a renderer zombies just get default handles, like falling blocks.
+        let mesh = world.get_resource_mut::<Assets<Mesh>>()
+            .map(|mut meshes| meshes.add(Mesh::from(shape::Box::new(ZOMBIE_SIZE.x, ZOMBIE_SIZE.y, ZOMBIE_SIZE.z))))
+            .unwrap_or_default();
+
+        let material = world.get_resource_mut::<Assets<StandardMaterial>>()
+            .map(|mut materials| materials.add(StandardMaterial::from(Color::rgb(0.2, 0.5, 0.2))))
+            .unwrap_or_default();
+
+        Self { mesh, material }
+    }
+}
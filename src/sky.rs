@@ -0,0 +1,83 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::GameState;
+
+
+// Consts
+// Time doesn't pass on its own, so it stays here unless something like the console's `time set` changes it.
+const DAY_START: f32 = 8.0;
+// Midday looks like the game always did before there were days, night just gets darker.
+const DAY_COLOR: Color = Color::rgb(0.4, 0.4, 0.4);
+const NIGHT_COLOR: Color = Color::rgb(0.02, 0.02, 0.06);
+const DAY_AMBIENT: f32 = 0.05;
+const NIGHT_AMBIENT: f32 = 0.005;
+
+// Plugin
+#[derive(Default)]
+pub struct SkyPlugin;
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .init_resource::<TimeOfDay>()
+
+         .add_enter_system(GameState::MainMenu, reset_time)
+         .add_system(update_sky);
+
+    }
+}
+
+// Systems
+fn reset_time (
+    mut time_of_day: ResMut<TimeOfDay>,
+) {
+    *time_of_day = TimeOfDay::default();
+}
+
+fn update_sky (
+    time_of_day: Res<TimeOfDay>,
+    mut clear_color: ResMut<ClearColor>,
+    mut ambient: ResMut<AmbientLight>,
+) {
+    if !time_of_day.is_changed() {
+        return;
+    }
+
+    let daylight = time_of_day.daylight();
+    let mix = |night: f32, day: f32| night + (day - night) * daylight;
+
+    clear_color.0 = Color::rgb(
+        mix(NIGHT_COLOR.r(), DAY_COLOR.r()),
+        mix(NIGHT_COLOR.g(), DAY_COLOR.g()),
+        mix(NIGHT_COLOR.b(), DAY_COLOR.b()),
+    );
+    ambient.brightness = mix(NIGHT_AMBIENT, DAY_AMBIENT);
+}
+
+// Resources
+/// Hours since midnight, from 0 up to 24.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeOfDay(f32);
+impl TimeOfDay {
+    pub fn hours(&self) -> f32 {
+        self.0
+    }
+
+    pub fn set_hours(&mut self, hours: f32) {
+        self.0 = hours.rem_euclid(24.0);
+    }
+
+    /// How light it is, from 0.0 at midnight to 1.0 through the middle of the day.
+    pub fn daylight(&self) -> f32 {
+        // The sun's height, with a bit of extra so it's fully light for a while around noon
+        let sun = ((self.0 - 6.0) / 24.0 * TAU).sin();
+        (sun * 1.5 + 0.5).clamp(0.0, 1.0)
+    }
+}
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self(DAY_START)
+    }
+}
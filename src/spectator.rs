@@ -0,0 +1,98 @@
+use bevy::{prelude::*, input::mouse::MouseWheel};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{actions::Action, player::Player, physics::{AabbCollider, Falls, Velocity}, keybinds::{Keybinds, BindInputs}};
+
+
+// Consts
+const DEFAULT_FLY_SPEED: f32 = 10.0;
+const MIN_FLY_SPEED: f32 = 1.0;
+const MAX_FLY_SPEED: f32 = 100.0;
+// Each notch of the scroll wheel multiplies fly speed by this much.
+const FLY_SPEED_STEP: f32 = 1.2;
+
+// Systems
+/// Toggles spectating for the player, stashing its physics components while it flies around.
+pub fn toggle_spectator (
+    mut commands: Commands,
+    inputs: BindInputs,
+    keybinds: Res<Keybinds>,
+
+    query: Query<(Entity, Option<&AabbCollider>, Option<&Falls>, Option<&Spectating>), With<Player>>,
+) {
+    if !inputs.just_pressed(&keybinds.toggle_spectator) {
+        return;
+    }
+
+    for (entity, opt_aabb, opt_falls, opt_spectating) in query.iter() {
+        if let Some(spectating) = opt_spectating {
+            let mut entity_commands = commands.entity(entity);
+            entity_commands.remove::<Spectating>();
+
+            if let Some(aabb) = spectating.stored_collider {
+                entity_commands.insert(aabb);
+            }
+            if spectating.had_falls {
+                entity_commands.insert(Falls);
+            }
+        }
+        else {
+            commands.entity(entity)
+                .insert(Spectating {
+                    speed: DEFAULT_FLY_SPEED,
+                    stored_collider: opt_aabb.copied(),
+                    had_falls: opt_falls.is_some(),
+                })
+                .insert(Velocity(Vec3::ZERO))
+                .remove::<AabbCollider>()
+                .remove::<Falls>();
+        }
+    }
+}
+
+/// Moves spectators straight from input. Runs after process_actions and overrides whatever velocity it set.
+pub fn fly (
+    mut scroll_evr: EventReader<MouseWheel>,
+
+    mut query: Query<(&ActionState<Action>, &Transform, &mut Velocity, &mut Spectating)>,
+) {
+    let scroll: f32 = scroll_evr.iter().map(|ev| ev.y.signum()).sum();
+
+    for (action_state, transform, mut velocity, mut spectating) in query.iter_mut() {
+        if scroll != 0.0 {
+            spectating.speed = (spectating.speed * FLY_SPEED_STEP.powf(scroll)).clamp(MIN_FLY_SPEED, MAX_FLY_SPEED);
+        }
+
+        let mut direction = Vec3::ZERO;
+        if action_state.pressed(Action::WalkForward) {
+            direction += -transform.local_z();
+        }
+        if action_state.pressed(Action::WalkBackward) {
+            direction += transform.local_z();
+        }
+        if action_state.pressed(Action::StrafeLeft) {
+            direction += -transform.local_x();
+        }
+        if action_state.pressed(Action::StrafeRight) {
+            direction += transform.local_x();
+        }
+        if action_state.pressed(Action::Jump) {
+            direction += Vec3::Y;
+        }
+        if action_state.pressed(Action::Crouch) {
+            direction -= Vec3::Y;
+        }
+
+        let speed = if action_state.pressed(Action::Sprint) {spectating.speed * 2.0} else {spectating.speed};
+        **velocity = direction.normalize_or_zero() * speed;
+    }
+}
+
+// Components
+/// Flying around with no collision. Holds the physics components to put back when we stop.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Spectating {
+    pub speed: f32,
+    stored_collider: Option<AabbCollider>,
+    had_falls: bool,
+}
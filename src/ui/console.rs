@@ -0,0 +1,554 @@
+use std::{collections::VecDeque, fs, str::FromStr};
+
+use bevy::{prelude::*, ecs::system::CommandQueue, utils::HashMap};
+use enum_map::EnumMap;
+use iyes_loopless::prelude::*;
+use leafwing_input_manager::prelude::{ActionState, InputMap};
+
+use crate::{
+    GameState,
+    actions::Action,
+    keybinds::{Keybinds, BindInputs},
+    map::{Block, BlockType, SetBlockEvent, SetBlockShape, WorldSeed, WorldConfig},
+    physics::{Gravity, Velocity},
+    player::Player,
+    items::Inventory,
+    setup::{ZombieAssets, spawn_zombie},
+    sky::TimeOfDay,
+    replay::Playback,
+    ui::UiAssets,
+};
+
+
+// Consts
+/// Run once for every world, as soon as it's playable. One command per line, and lines starting with # are skipped.
+pub const CONSOLE_SCRIPT_PATH: &str = "startup.console";
+// Lines of output kept on screen
+const CONSOLE_LINES: usize = 12;
+// So a script that runs itself can't hang the game
+const MAX_COMMANDS_PER_FRAME: usize = 256;
+// Biggest area edit in one go, from fill or the world edit commands, so a typo can't freeze the game
+pub const MAX_FILL_VOLUME: i32 = 32768;
+const CONSOLE_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.7);
+
+// Plugin
+#[derive(Default)]
+pub struct ConsolePlugin;
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .init_resource::<Console>()
+
+         .add_console_command("tp", "tp <x> <y> <z>", tp)
+         .add_console_command("setblock", "setblock <x> <y> <z> <block>", setblock)
+         .add_console_command("fill", "fill <x1> <y1> <z1> <x2> <y2> <z2> <block>", fill)
+         .add_console_command("give", "give <block> [count]", give)
+         .add_console_command("time", "time [set <hours|day|noon|night|midnight>]", time)
+         .add_console_command("gravity", "gravity [<y> | <x> <y> <z>]", gravity)
+         .add_console_command("spawn", "spawn zombie [<x> <y> <z>]", spawn)
+         .add_console_command("seed", "seed", seed)
+         .add_console_command("exec", "exec <path>", exec)
+
+         .add_enter_system(GameState::Playing, queue_startup_script)
+         .add_enter_system(GameState::MainMenu, close_console)
+
+         .add_system(toggle_console.run_in_state(GameState::Playing))
+         .add_system(console_input.after(toggle_console))
+         .add_system(update_console_text.after(console_input))
+         .add_system(run_console_commands.exclusive_system().at_end());
+
+    }
+}
+
+pub trait AppConsoleExt {
+    /// Adds a command to the console. `usage` is what `help` shows for it.
+    fn add_console_command(&mut self, name: &'static str, usage: &'static str, run: CommandFn) -> &mut Self;
+}
+impl AppConsoleExt for App {
+    fn add_console_command(&mut self, name: &'static str, usage: &'static str, run: CommandFn) -> &mut Self {
+        self.init_resource::<CommandRegistry>();
+        self.world.resource_mut::<CommandRegistry>().commands.insert(name, ConsoleCommand { usage, run });
+        self
+    }
+}
+
+// Systems
+/// Only the first time a world starts playing, not every time it's unpaused.
+fn queue_startup_script (
+    mut console: ResMut<Console>,
+) {
+    if console.script_queued {
+        return;
+    }
+    console.script_queued = true;
+
+    // Not having one is fine, it's only there if someone wants it.
+    if fs::metadata(CONSOLE_SCRIPT_PATH).is_err() {
+        return;
+    }
+
+    match script_lines(CONSOLE_SCRIPT_PATH) {
+        Ok(lines) => console.pending.extend(lines),
+        Err(error) => console.print(error),
+    }
+}
+
+fn close_console (
+    mut commands: Commands,
+    mut console: ResMut<Console>,
+
+    root_query: Query<Entity, With<ConsoleRoot>>,
+) {
+    console.open = false;
+    console.script_queued = false;
+
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Opening the console takes the keyboard away from the player until it's closed again.
+fn toggle_console (
+    mut commands: Commands,
+    mut windows: ResMut<Windows>,
+    mut console: ResMut<Console>,
+    keybinds: Res<Keybinds>,
+    gamepads: Res<Gamepads>,
+    inputs: BindInputs,
+    ui_assets: Res<UiAssets>,
+    opt_playback: Option<Res<Playback>>,
+
+    mut player_query: Query<(Entity, &mut ActionState<Action>), With<Player>>,
+    root_query: Query<Entity, With<ConsoleRoot>>,
+) {
+    if !inputs.just_pressed(&keybinds.console) {
+        return;
+    }
+
+    console.open = !console.open;
+
+    if console.open {
+        if let Some(window) = windows.get_primary_mut() {
+            window.set_cursor_lock_mode(false);
+            window.set_cursor_visibility(true);
+        }
+
+        for (entity, mut action_state) in player_query.iter_mut() {
+            commands.entity(entity).remove::<InputMap<Action>>();
+            *action_state = ActionState::default();
+        }
+
+        spawn_console(&mut commands, ui_assets.font.clone(), &console);
+    }
+    else {
+        // A replay already has the player's input map, and hands it back when it's done.
+        if opt_playback.is_none() {
+            for (entity, _) in player_query.iter() {
+                let mut input_map = keybinds.input_map();
+                if let Some(gamepad) = gamepads.iter().next() {
+                    input_map.set_gamepad(*gamepad);
+                }
+                commands.entity(entity).insert(input_map);
+            }
+        }
+
+        for entity in root_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn console_input (
+    mut chars: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut console: ResMut<Console>,
+) {
+    // Read even when closed, so what was typed before opening doesn't show up.
+    let typed: String = chars.iter()
+        .map(|ev| ev.char)
+        // The backtick is what opened us
+        .filter(|c| !c.is_control() && *c != '`')
+        .collect();
+
+    if !console.open {
+        return;
+    }
+
+    if !typed.is_empty() {
+        console.input.push_str(&typed);
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        if !line.trim().is_empty() {
+            console.print(format!("> {}", line));
+            console.pending.push_back(line);
+        }
+    }
+}
+
+fn update_console_text (
+    console: Res<Console>,
+
+    mut query: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = console_text(&console);
+    }
+}
+
+/// Runs whatever's been entered or scripted. Commands get the whole world to poke at, so this is exclusive.
+fn run_console_commands (world: &mut World) {
+    // Checked first, since touching the console mutably would mark it as changed every frame.
+    if world.resource::<Console>().pending.is_empty() {
+        return;
+    }
+
+    for _ in 0..MAX_COMMANDS_PER_FRAME {
+        let line = match world.resource_mut::<Console>().pending.pop_front() {
+            Some(line) => line,
+            None => return,
+        };
+
+        let result = world.resource_scope(|world, registry: Mut<CommandRegistry>| registry.run(world, &line));
+
+        let mut console = world.resource_mut::<Console>();
+        match result {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => console.print(output),
+            Err(error) => console.print(format!("{}: {}", line.trim(), error)),
+        }
+    }
+}
+
+// Commands
+fn tp(world: &mut World, args: &[&str]) -> CommandResult {
+    let [x, y, z] = match args {
+        [x, y, z] => [*x, *y, *z],
+        _ => return Err(usage("tp <x> <y> <z>")),
+    };
+
+    let player = find_player(world).ok_or("no player to teleport")?;
+    let current = world.get::<Transform>(player).map_or(Vec3::ZERO, |transform| transform.translation);
+    let position = Vec3::new(parse_coord(x, current.x)?, parse_coord(y, current.y)?, parse_coord(z, current.z)?);
+
+    if let Some(mut transform) = world.get_mut::<Transform>(player) {
+        transform.translation = position;
+    }
+    if let Some(mut velocity) = world.get_mut::<Velocity>(player) {
+        **velocity = Vec3::ZERO;
+    }
+
+    Ok(format!("Teleported to {:.1} {:.1} {:.1}", position.x, position.y, position.z))
+}
+
+fn setblock(world: &mut World, args: &[&str]) -> CommandResult {
+    let (position, block_type) = match args {
+        [x, y, z, block] => (parse_block_position(world, [x, y, z])?, parse_block_type(block)?),
+        _ => return Err(usage("setblock <x> <y> <z> <block>")),
+    };
+
+    if !world.resource::<WorldConfig>().is_editable(position) {
+        return Err(format!("{} is outside the world's height", position));
+    }
+
+    world.resource_mut::<Events<SetBlockEvent>>().send(SetBlockEvent { shape: SetBlockShape::Block(position), block: Block::new(block_type) });
+    Ok(format!("Set {} to {:?}", position, block_type))
+}
+
+fn fill(world: &mut World, args: &[&str]) -> CommandResult {
+    let (from, to, block_type) = match args {
+        [x1, y1, z1, x2, y2, z2, block] => (
+            parse_block_position(world, [x1, y1, z1])?,
+            parse_block_position(world, [x2, y2, z2])?,
+            parse_block_type(block)?,
+        ),
+        _ => return Err(usage("fill <x1> <y1> <z1> <x2> <y2> <z2> <block>")),
+    };
+
+    let shape = SetBlockShape::Range(from, to);
+    let (min, max) = shape.bounds();
+    let volume = check_volume(min, max)?;
+
+    world.resource_mut::<Events<SetBlockEvent>>().send(SetBlockEvent { shape, block: Block::new(block_type) });
+    Ok(format!("Filled {} blocks with {:?}", volume, block_type))
+}
+
+fn give(world: &mut World, args: &[&str]) -> CommandResult {
+    let (block_type, count) = match args {
+        [block] => (parse_block_type(block)?, 1),
+        [block, count] => (parse_block_type(block)?, parse::<u32>(count, "count")?),
+        _ => return Err(usage("give <block> [count]")),
+    };
+
+    let player = find_player(world).ok_or("no player to give to")?;
+    let mut inventory = world.get_mut::<Inventory>(player).ok_or("the player has no inventory")?;
+
+    let given = (0..count).take_while(|_| inventory.add_block(block_type)).count();
+    if given == 0 {
+        return Err("the inventory is full".to_string());
+    }
+
+    Ok(format!("Gave {} {:?}", given, block_type))
+}
+
+fn time(world: &mut World, args: &[&str]) -> CommandResult {
+    let hours = match args {
+        [] => return Ok(format!("It's {:.1} hours", world.resource::<TimeOfDay>().hours())),
+        ["set", "day"] => 8.0,
+        ["set", "noon"] => 12.0,
+        ["set", "night"] => 20.0,
+        ["set", "midnight"] => 0.0,
+        ["set", hours] => parse_float(hours, "hours")?,
+        _ => return Err(usage("time [set <hours|day|noon|night|midnight>]")),
+    };
+
+    world.resource_mut::<TimeOfDay>().set_hours(hours);
+    Ok(format!("Set the time to {:.1} hours", world.resource::<TimeOfDay>().hours()))
+}
+
+fn gravity(world: &mut World, args: &[&str]) -> CommandResult {
+    let new = match args {
+        [] => return Ok(format!("Gravity is {}", **world.resource::<Gravity>())),
+        [y] => Vec3::new(0.0, parse_float(y, "y")?, 0.0),
+        [x, y, z] => Vec3::new(parse_float(x, "x")?, parse_float(y, "y")?, parse_float(z, "z")?),
+        _ => return Err(usage("gravity [<y> | <x> <y> <z>]")),
+    };
+
+    **world.resource_mut::<Gravity>() = new;
+    Ok(format!("Set gravity to {}", new))
+}
+
+fn spawn(world: &mut World, args: &[&str]) -> CommandResult {
+    let position = match args {
+        ["zombie"] => {
+            // A few blocks in front of the player
+            let player = find_player(world).ok_or("no player to spawn in front of, give a position")?;
+            let transform = world.get::<Transform>(player).copied().unwrap_or_default();
+            transform.translation + transform.forward() * 3.0 + Vec3::Y
+        }
+        ["zombie", x, y, z] => {
+            let current = find_player(world)
+                .and_then(|player| world.get::<Transform>(player))
+                .map_or(Vec3::ZERO, |transform| transform.translation);
+            Vec3::new(parse_coord(x, current.x)?, parse_coord(y, current.y)?, parse_coord(z, current.z)?)
+        }
+        [kind, ..] if *kind != "zombie" => return Err(format!("don't know how to spawn {}", kind)),
+        _ => return Err(usage("spawn zombie [<x> <y> <z>]")),
+    };
+
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    spawn_zombie(&mut commands, world.resource::<ZombieAssets>(), position);
+    queue.apply(world);
+
+    Ok(format!("Spawned a zombie at {:.1} {:.1} {:.1}", position.x, position.y, position.z))
+}
+
+fn seed(world: &mut World, _args: &[&str]) -> CommandResult {
+    match world.get_resource::<WorldSeed>() {
+        Some(seed) => Ok(format!("Seed: {}", **seed)),
+        None => Err("no world loaded".to_string()),
+    }
+}
+
+fn exec(world: &mut World, args: &[&str]) -> CommandResult {
+    let path = match args {
+        [path] => *path,
+        _ => return Err(usage("exec <path>")),
+    };
+
+    let lines = script_lines(path)?;
+    let count = lines.len();
+    world.resource_mut::<Console>().pending.extend(lines);
+
+    Ok(format!("Running {} commands from {}", count, path))
+}
+
+// Helper functions
+/// Spawned with the console's text already in, since update_console_text only catches up when the console changes.
+fn spawn_console(commands: &mut Commands, font: Handle<Font>, console: &Console) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(0.0), top: Val::Px(0.0), ..default() },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                padding: Rect::all(Val::Px(6.0)),
+                ..default()
+            },
+            color: CONSOLE_COLOR.into(),
+            ..default()
+        })
+        .insert(ConsoleRoot)
+        .with_children(|root| {
+            root.spawn_bundle(TextBundle {
+                text: Text::with_section(console_text(console), TextStyle { font, font_size: 16.0, color: Color::WHITE }, default()),
+                ..default()
+            })
+            .insert(ConsoleText);
+        });
+}
+
+/// The log, then what's being typed.
+fn console_text(console: &Console) -> String {
+    format!("{}\n> {}_", console.log.join("\n"), console.input)
+}
+
+/// A script's commands, without blank lines or comments.
+fn script_lines(path: &str) -> Result<Vec<String>, String> {
+    let contents = fs::read_to_string(path).map_err(|error| format!("Couldn't read {}: {}", path, error))?;
+
+    Ok(contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+pub fn find_player(world: &mut World) -> Option<Entity> {
+    world.query_filtered::<Entity, With<Player>>().iter(world).next()
+}
+
+pub fn usage(usage: &str) -> String {
+    format!("usage: {}", usage)
+}
+
+/// How many blocks a box spans on each axis, counting both ends.
+/// In i64, since the corners can be anywhere an i32 goes and `max - min` would overflow.
+pub fn box_size(min: IVec3, max: IVec3) -> [i64; 3] {
+    [0, 1, 2].map(|axis| max[axis] as i64 - min[axis] as i64 + 1)
+}
+
+/// Returns how many blocks are in the box, or an error if that's more than MAX_FILL_VOLUME.
+pub fn check_volume(min: IVec3, max: IVec3) -> Result<i64, String> {
+    let [x, y, z] = box_size(min, max);
+    let volume = x.saturating_mul(y).saturating_mul(z);
+
+    if volume > MAX_FILL_VOLUME as i64 {
+        return Err(format!("{}x{}x{} blocks is too many, the most is {}", x, y, z, MAX_FILL_VOLUME));
+    }
+
+    Ok(volume)
+}
+
+pub fn parse<T: FromStr>(arg: &str, what: &str) -> Result<T, String> {
+    arg.parse().map_err(|_| format!("{} isn't a valid {}", arg, what))
+}
+
+/// Like parse, but without the `nan` and `inf` that Rust happily parses as floats.
+pub fn parse_float(arg: &str, what: &str) -> Result<f32, String> {
+    let value = parse::<f32>(arg, what)?;
+
+    if value.is_finite() {Ok(value)} else {Err(format!("{} isn't a valid {}", arg, what))}
+}
+
+/// A number, or `~` for where the player is, or `~n` for n away from it.
+pub fn parse_coord(arg: &str, current: f32) -> Result<f32, String> {
+    let coord = match arg.strip_prefix('~') {
+        Some("") => current,
+        Some(offset) => current + parse_float(offset, "offset")?,
+        None => parse_float(arg, "coordinate")?,
+    };
+
+    if coord.is_finite() {Ok(coord)} else {Err(format!("{} is too far away", arg))}
+}
+
+pub fn parse_block_position(world: &mut World, [x, y, z]: [&&str; 3]) -> Result<IVec3, String> {
+    let current = find_player(world)
+        .and_then(|player| world.get::<Transform>(player))
+        .map_or(Vec3::ZERO, |transform| transform.translation);
+
+    Ok(Vec3::new(parse_coord(x, current.x)?, parse_coord(y, current.y)?, parse_coord(z, current.z)?).round().as_ivec3())
+}
+
+/// Block names are the same as in the code, but any case and with underscores allowed, so `gold_ore` works.
+pub fn parse_block_type(arg: &str) -> Result<BlockType, String> {
+    let wanted = arg.replace('_', "").to_lowercase();
+
+    EnumMap::<BlockType, ()>::default()
+        .iter()
+        .map(|(block_type, _)| block_type)
+        .find(|block_type| format!("{:?}", block_type).to_lowercase() == wanted)
+        .ok_or_else(|| format!("there's no block called {}", arg))
+}
+
+// Components
+#[derive(Component)]
+pub struct ConsoleRoot;
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+// Resources
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    log: Vec<String>,
+    // Lines waiting for run_console_commands
+    pending: VecDeque<String>,
+    // Whether this world's startup script has been queued yet
+    script_queued: bool,
+}
+impl Console {
+    /// Queues a line to be run as if it had been typed in.
+    pub fn run(&mut self, line: impl Into<String>) {
+        self.pending.push_back(line.into());
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        info!("{}", line);
+
+        self.log.push(line);
+        if self.log.len() > CONSOLE_LINES {
+            self.log.remove(0);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: HashMap<&'static str, ConsoleCommand>,
+}
+impl CommandRegistry {
+    /// Splits a line on whitespace and runs the command its first word names. `help` is always there, and lists everything else.
+    pub fn run(&self, world: &mut World, line: &str) -> CommandResult {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Ok(String::new()),
+        };
+
+        if name == "help" {
+            let mut usages: Vec<&str> = self.commands.values().map(|command| command.usage).collect();
+            usages.sort_unstable();
+            return Ok(format!("help\n{}", usages.join("\n")));
+        }
+
+        match self.commands.get(name) {
+            Some(command) => (command.run)(world, args),
+            None => Err("unknown command, try help".to_string()),
+        }
+    }
+}
+
+// Data
+/// What a command printed if it worked, or what went wrong if it didn't.
+pub type CommandResult = Result<String, String>;
+pub type CommandFn = fn(&mut World, &[&str]) -> CommandResult;
+
+#[derive(Clone, Copy)]
+pub struct ConsoleCommand {
+    pub usage: &'static str,
+    pub run: CommandFn,
+}
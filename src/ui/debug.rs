@@ -0,0 +1,202 @@
+use bevy::{
+    prelude::*,
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    pbr::{NotShadowCaster, wireframe::{WireframeConfig, WireframePlugin}},
+};
+use bevy_inspector_egui::{WorldInspectorPlugin, RegisterInspectable};
+
+use crate::{ui::UiAssets, hud::outline_mesh, player::Player, physics::{AabbCollider, Velocity}, map::LoadedChunks, coords::CHUNK_SIZE, keybinds::{Keybinds, BindInputs}};
+
+
+// Plugin
+#[derive(Default)]
+pub struct DebugPlugin;
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .add_plugin(FrameTimeDiagnosticsPlugin)
+         .add_plugin(WireframePlugin)
+         .add_plugin(WorldInspectorPlugin::new())
+         //.add_plugin(bevy_rapier3d::prelude::RapierDebugRenderPlugin::default())
+         .register_inspectable::<Velocity>()
+         .init_resource::<DebugOverlay>()
+         .init_resource::<DebugAssets>()
+         .add_startup_system(spawn_overlay)
+         .add_system(toggle_debug)
+         .add_system(update_overlay.after(toggle_debug))
+         .add_system(draw_debug_boxes.after(toggle_debug));
+
+    }
+}
+
+// Systems
+fn spawn_overlay (
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section("", TextStyle { font: ui_assets.font.clone(), font_size: 16.0, color: Color::WHITE }, default()),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(8.0), top: Val::Px(8.0), ..default() },
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(OverlayText);
+}
+
+fn toggle_debug (
+    keybinds: Res<Keybinds>,
+    inputs: BindInputs,
+    mut overlay: ResMut<DebugOverlay>,
+    mut wireframe_config: ResMut<WireframeConfig>,
+) {
+    if inputs.just_pressed(&keybinds.toggle_overlay) {
+        overlay.visible = !overlay.visible;
+    }
+    if inputs.just_pressed(&keybinds.toggle_chunk_borders) {
+        overlay.chunk_borders = !overlay.chunk_borders;
+    }
+    if inputs.just_pressed(&keybinds.toggle_aabbs) {
+        overlay.aabbs = !overlay.aabbs;
+    }
+    if inputs.just_pressed(&keybinds.toggle_wireframe) {
+        wireframe_config.global = !wireframe_config.global;
+    }
+}
+
+fn update_overlay (
+    overlay: Res<DebugOverlay>,
+    diagnostics: Res<Diagnostics>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    player_query: Query<&Transform, With<Player>>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<OverlayText>>,
+) {
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        visibility.is_visible = overlay.visible;
+        if !overlay.visible {
+            continue;
+        }
+
+        let fps = diagnostics
+            .get(FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.average())
+            .unwrap_or(0.0);
+
+        let pending_meshes = loaded_chunks.values().filter(|chunk| !chunk.meshed).count();
+        let chunk_memory: usize = loaded_chunks.values().map(|chunk| chunk.blocks().memory_usage()).sum();
+
+        let mut lines = vec![
+            format!("FPS: {:.0}", fps),
+            format!("Loaded chunks: {}", loaded_chunks.len()),
+            format!("Pending meshes: {}", pending_meshes),
+            format!("Block memory: {} KiB", chunk_memory / 1024),
+        ];
+
+        if let Ok(transform) = player_query.get_single() {
+            let (chunk, block) = LoadedChunks::index_block(transform.translation.round().as_ivec3());
+            lines.push(format!("Position: {:.2} {:.2} {:.2}", transform.translation.x, transform.translation.y, transform.translation.z));
+            lines.push(format!("Chunk: {} {} {}  Block in chunk: {} {} {}", chunk.x, chunk.y, chunk.z, block.x, block.y, block.z));
+        }
+
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+/// Keeps a line box around every loaded chunk and collider. Boxes are reused from frame to frame, and only spawned or despawned when the count changes.
+fn draw_debug_boxes (
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    debug_assets: Res<DebugAssets>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    mut box_query: Query<(Entity, &mut Transform, &mut Handle<StandardMaterial>), (With<DebugBox>, Without<AabbCollider>)>,
+    aabb_query: Query<(&Transform, &AabbCollider)>,
+) {
+    let mut boxes = Vec::new();
+    let chunk_size = Vec3::splat(CHUNK_SIZE as f32);
+
+    if overlay.chunk_borders {
+        for index in loaded_chunks.keys() {
+            // Blocks are centered on whole numbers, so the chunk starts half a block before its first one.
+            let center = index.as_vec3() * chunk_size + chunk_size * 0.5 - Vec3::splat(0.5);
+            boxes.push((&debug_assets.chunk_material, center, chunk_size));
+        }
+    }
+
+    if overlay.aabbs {
+        for (transform, aabb) in aabb_query.iter() {
+            let aabb = AabbCollider::add_location(transform.translation, aabb);
+            boxes.push((&debug_assets.aabb_material, aabb.get_center(), aabb.get_extents()));
+        }
+    }
+
+    let mut boxes = boxes.into_iter();
+    for (entity, mut transform, mut material) in box_query.iter_mut() {
+        match boxes.next() {
+            Some((box_material, center, size)) => {
+                let box_transform = Transform::from_translation(center).with_scale(size);
+                if *transform != box_transform {
+                    *transform = box_transform;
+                }
+                if *material != *box_material {
+                    *material = box_material.clone();
+                }
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for (material, center, size) in boxes {
+        spawn_box(&mut commands, material, &debug_assets, center, size);
+    }
+}
+
+// Helper functions
+fn spawn_box(commands: &mut Commands, material: &Handle<StandardMaterial>, debug_assets: &DebugAssets, center: Vec3, size: Vec3) {
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: debug_assets.box_mesh.clone(),
+            material: material.clone(),
+            transform: Transform::from_translation(center).with_scale(size),
+            ..default()
+        })
+        .insert(NotShadowCaster)
+        .insert(DebugBox);
+}
+
+// Resources
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    pub chunk_borders: bool,
+    pub aabbs: bool,
+}
+
+pub struct DebugAssets {
+    box_mesh: Handle<Mesh>,
+    chunk_material: Handle<StandardMaterial>,
+    aabb_material: Handle<StandardMaterial>,
+}
+impl FromWorld for DebugAssets {
+    fn from_world(world: &mut World) -> Self {
+        let box_mesh = world.resource_mut::<Assets<Mesh>>().add(outline_mesh(1.0));
+
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let chunk_material = materials.add(StandardMaterial { base_color: Color::YELLOW, unlit: true, ..default() });
+        let aabb_material = materials.add(StandardMaterial { base_color: Color::CYAN, unlit: true, ..default() });
+
+        Self { box_mesh, chunk_material, aabb_material }
+    }
+}
+
+// Components
+#[derive(Component)]
+pub struct OverlayText;
+
+#[derive(Component)]
+pub struct DebugBox;
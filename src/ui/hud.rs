@@ -0,0 +1,352 @@
+use bevy::{
+    prelude::*,
+    pbr::NotShadowCaster,
+    render::{mesh::{Indices, VertexAttributeValues}, render_resource::PrimitiveTopology},
+};
+
+use crate::{ui::UiAssets, items::{Inventory, HOTBAR_SIZE}, player::{Player, Health, LookTarget}, map::LoadedChunks};
+
+
+// Consts
+const SLOT_SIZE: f32 = 48.0;
+const SLOT_COLOR: Color = Color::rgba(0.1, 0.1, 0.1, 0.6);
+const SELECTED_SLOT_COLOR: Color = Color::rgba(0.6, 0.6, 0.6, 0.8);
+const BAR_BACKGROUND_COLOR: Color = Color::rgba(0.1, 0.1, 0.1, 0.6);
+// Slightly bigger than a block so the outline doesn't z-fight with its faces.
+const OUTLINE_SIZE: f32 = 1.01;
+
+// Plugin
+#[derive(Default)]
+pub struct HudPlugin;
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .init_resource::<HudAssets>();
+
+    }
+}
+
+// Systems
+pub fn spawn_hud (
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    hud_assets: Res<HudAssets>,
+) {
+    let text_style = TextStyle {
+        font: ui_assets.font.clone(),
+        font_size: 12.0,
+        color: Color::WHITE,
+    };
+
+    // Crosshair
+    for size in [Size::new(Val::Px(16.0), Val::Px(2.0)), Size::new(Val::Px(2.0), Val::Px(16.0))] {
+        commands
+            .spawn_bundle(NodeBundle {
+                style: centered(size, 0.0),
+                color: Color::WHITE.into(),
+                ..default()
+            })
+            .insert(HudElement);
+    }
+
+    // Mining progress, just under the crosshair
+    commands
+        .spawn_bundle(NodeBundle {
+            style: centered(Size::new(Val::Px(40.0), Val::Px(4.0)), 16.0),
+            color: BAR_BACKGROUND_COLOR.into(),
+            ..default()
+        })
+        .insert(HudElement)
+        .insert(MiningBar)
+        .with_children(|bar| {
+            bar.spawn_bundle(NodeBundle {
+                style: Style { size: Size::new(Val::Percent(0.0), Val::Percent(100.0)), ..default() },
+                color: Color::WHITE.into(),
+                ..default()
+            })
+            .insert(HudElement)
+            .insert(MiningFill);
+        });
+
+    // Health bar, sitting on top of the hotbar
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(8.0), bottom: Val::Px(SLOT_SIZE + 16.0), ..default() },
+                size: Size::new(Val::Px(200.0), Val::Px(12.0)),
+                ..default()
+            },
+            color: BAR_BACKGROUND_COLOR.into(),
+            ..default()
+        })
+        .insert(HudElement)
+        .with_children(|bar| {
+            bar.spawn_bundle(NodeBundle {
+                style: Style { size: Size::new(Val::Percent(100.0), Val::Percent(100.0)), ..default() },
+                color: Color::rgb(0.8, 0.1, 0.1).into(),
+                ..default()
+            })
+            .insert(HudElement)
+            .insert(HealthFill);
+        });
+
+    // Hotbar
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(8.0), bottom: Val::Px(8.0), ..default() },
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(HudElement)
+        .with_children(|hotbar| {
+            for slot in 0..HOTBAR_SIZE {
+                hotbar
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(SLOT_SIZE), Val::Px(SLOT_SIZE)),
+                            margin: Rect::all(Val::Px(2.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: SLOT_COLOR.into(),
+                        ..default()
+                    })
+                    .insert(HudElement)
+                    .insert(HotbarSlot(slot))
+                    .with_children(|slot_node| {
+                        slot_node
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section("", text_style.clone(), TextAlignment { horizontal: HorizontalAlign::Center, ..default() }),
+                                ..default()
+                            })
+                            .insert(HudElement)
+                            .insert(HotbarSlot(slot));
+                    });
+            }
+        });
+
+    // Gold counter
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section("Gold: 0", TextStyle { font_size: 24.0, color: Color::GOLD, ..text_style }, default()),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { right: Val::Px(8.0), top: Val::Px(8.0), ..default() },
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudElement)
+        .insert(GoldLabel);
+
+    // Outline around whatever block we're looking at
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: hud_assets.outline_mesh.clone(),
+            material: hud_assets.outline_material.clone(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(NotShadowCaster)
+        .insert(BlockOutline);
+}
+
+pub fn despawn_hud (
+    mut commands: Commands,
+
+    query: Query<Entity, Or<(With<HudElement>, With<BlockOutline>)>>,
+) {
+    // Children are HudElements too, so despawning everything individually is enough.
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Hides the HUD while the cursor's unlocked. The mining bar is left to `update_block_outline`, which hides it then too.
+pub fn hud_visibility (
+    windows: Res<Windows>,
+
+    mut query: Query<&mut Visibility, (With<HudElement>, Without<MiningBar>, Without<MiningFill>)>,
+) {
+    let visible = windows.get_primary().map_or(false, |window| window.cursor_locked());
+
+    for mut visibility in query.iter_mut() {
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
+}
+
+pub fn update_block_outline (
+    windows: Res<Windows>,
+    look_target: Res<LookTarget>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    mut outline_query: Query<(&mut Transform, &mut Visibility), With<BlockOutline>>,
+    mut bar_query: Query<&mut Visibility, (Or<(With<MiningBar>, With<MiningFill>)>, Without<BlockOutline>)>,
+    mut fill_query: Query<&mut Style, With<MiningFill>>,
+) {
+    let cursor_locked = windows.get_primary().map_or(false, |window| window.cursor_locked());
+
+    let target = look_target.filter(|_| cursor_locked);
+
+    for (mut transform, mut visibility) in outline_query.iter_mut() {
+        visibility.is_visible = target.is_some();
+        if let Some(hit) = target {
+            transform.translation = hit.position.as_vec3();
+        }
+    }
+
+    let progress = target
+        .and_then(|hit| loaded_chunks.get_block(hit.position))
+        .map_or(0.0, |block| (block.damage() / block.block_type().hardness()).clamp(0.0, 1.0));
+
+    // Only show the progress bar once we've started on a block.
+    for mut visibility in bar_query.iter_mut() {
+        if visibility.is_visible != (progress > 0.0) {
+            visibility.is_visible = progress > 0.0;
+        }
+    }
+    for mut style in fill_query.iter_mut() {
+        style.size.width = Val::Percent(progress * 100.0);
+    }
+}
+
+pub fn update_hud (
+    player_query: Query<(&Health, &Inventory), With<Player>>,
+
+    mut health_query: Query<&mut Style, With<HealthFill>>,
+    mut slot_query: Query<(&HotbarSlot, &mut UiColor)>,
+    mut label_query: Query<(&HotbarSlot, &mut Text), Without<GoldLabel>>,
+    mut gold_query: Query<&mut Text, With<GoldLabel>>,
+) {
+    let (health, inventory) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    // Only write what actually changed, so the UI isn't relaid out every frame.
+    let health_width = Val::Percent((health.current / health.max).clamp(0.0, 1.0) * 100.0);
+    for mut style in health_query.iter_mut() {
+        if style.size.width != health_width {
+            style.size.width = health_width;
+        }
+    }
+
+    for (slot, mut color) in slot_query.iter_mut() {
+        let slot_color = if **slot == inventory.selected {SELECTED_SLOT_COLOR} else {SLOT_COLOR};
+        if color.0 != slot_color {
+            *color = slot_color.into();
+        }
+    }
+
+    for (slot, mut text) in label_query.iter_mut() {
+        let label = match &inventory.slots[**slot] {
+            Some(stack) => format!("{:?}\n{}", stack.block_type, stack.count),
+            None => String::new(),
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+
+    let gold_label = format!("Gold: {}", inventory.gold);
+    for mut text in gold_query.iter_mut() {
+        if text.sections[0].value != gold_label {
+            text.sections[0].value = gold_label.clone();
+        }
+    }
+}
+
+// Helper functions
+/// Style for a node centred on the screen, nudged down by `offset` pixels.
+fn centered(size: Size<Val>, offset: f32) -> Style {
+    let (width, height) = match (size.width, size.height) {
+        (Val::Px(width), Val::Px(height)) => (width, height),
+        _ => (0.0, 0.0),
+    };
+
+    Style {
+        position_type: PositionType::Absolute,
+        position: Rect { left: Val::Percent(50.0), top: Val::Percent(50.0), ..default() },
+        margin: Rect { left: Val::Px(width * -0.5), top: Val::Px(height * -0.5 + offset), ..default() },
+        size,
+        ..default()
+    }
+}
+
+/// The twelve edges of a cube as a line list.
+pub fn outline_mesh(size: f32) -> Mesh {
+    let half = size * 0.5;
+    let corners: Vec<[f32; 3]> = (0..8)
+        .map(|i| [
+            if i & 1 == 0 {-half} else {half},
+            if i & 2 == 0 {-half} else {half},
+            if i & 4 == 0 {-half} else {half},
+        ])
+        .collect();
+
+    // Every pair of corners that differ on exactly one axis
+    let mut indices = Vec::with_capacity(24);
+    for a in 0..8u32 {
+        for axis in [1, 2, 4] {
+            if a & axis == 0 {
+                indices.extend_from_slice(&[a, a | axis]);
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, VertexAttributeValues::Float32x3(vec![[0.0, 1.0, 0.0]; corners.len()]));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, VertexAttributeValues::Float32x2(vec![[0.0; 2]; corners.len()]));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, VertexAttributeValues::Float32x3(corners));
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+// Resources
+pub struct HudAssets {
+    pub outline_mesh: Handle<Mesh>,
+    pub outline_material: Handle<StandardMaterial>,
+}
+impl FromWorld for HudAssets {
+    fn from_world(world: &mut World) -> Self {
+        let outline_mesh = world.resource_mut::<Assets<Mesh>>().add(outline_mesh(OUTLINE_SIZE));
+        let outline_material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+            base_color: Color::WHITE,
+            unlit: true,
+            ..default()
+        });
+
+        Self { outline_mesh, outline_material }
+    }
+}
+
+// Components
+/// Everything on the HUD. Visibility isn't inherited, so every node gets one of these.
+#[derive(Component)]
+pub struct HudElement;
+
+#[derive(Component)]
+pub struct BlockOutline;
+
+#[derive(Component)]
+pub struct MiningBar;
+
+#[derive(Component)]
+pub struct MiningFill;
+
+#[derive(Component)]
+pub struct HealthFill;
+
+#[derive(Component, Deref)]
+pub struct HotbarSlot(usize);
+
+#[derive(Component)]
+pub struct GoldLabel;
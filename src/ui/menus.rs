@@ -0,0 +1,309 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::{prelude::*, app::AppExit, asset::LoadState};
+use iyes_loopless::state::NextState;
+
+use crate::{GameState, ui::UiAssets, keybinds::{Keybinds, BindInputs}, rebind_menu::{RebindMenu, spawn_menu}, map::{LoadedChunks, WorldSeed, WorldConfig}, save::SavedWorld, player::Player, client::{NetClient, ServerAddress}};
+
+
+// Consts
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+
+// Systems
+/// Runs during GameState::Loading. Heads to the main menu once the UI font is in.
+pub fn check_assets_loaded (
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    ui_assets: Res<UiAssets>,
+) {
+    match asset_server.get_load_state(&ui_assets.font) {
+        LoadState::Loaded => {
+            commands.insert_resource(NextState(GameState::MainMenu));
+        }
+        LoadState::Failed => {
+            warn!("Couldn't load the UI font, menus won't have any text");
+            commands.insert_resource(NextState(GameState::MainMenu));
+        }
+        _ => {}
+    }
+}
+
+pub fn spawn_main_menu (
+    mut commands: Commands,
+    mut windows: ResMut<Windows>,
+    ui_assets: Res<UiAssets>,
+) {
+    unlock_cursor(&mut windows);
+
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    commands.insert_resource(SeedInput(seed.to_string()));
+
+    let text_style = text_style(&ui_assets);
+
+    commands
+        .spawn_bundle(menu_root())
+        .insert(MenuRoot)
+        .with_children(|root| {
+            root.spawn_bundle(TextBundle {
+                text: Text::with_section("3D ZOMBIES GOLD", TextStyle { font_size: 48.0, ..text_style.clone() }, default()),
+                style: Style { margin: Rect::all(Val::Px(24.0)), ..default() },
+                ..default()
+            });
+
+            root
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(format!("Seed: {}", seed), text_style.clone(), default()),
+                    style: Style { margin: Rect::all(Val::Px(8.0)), ..default() },
+                    ..default()
+                })
+                .insert(SeedLabel);
+
+            spawn_button(root, "New World", MenuButton::NewWorld, &text_style);
+            if SavedWorld::exists() {
+                spawn_button(root, "Load World", MenuButton::LoadWorld, &text_style);
+            }
+            spawn_button(root, "Join Server", MenuButton::JoinServer, &text_style);
+            spawn_button(root, "Settings", MenuButton::Settings, &text_style);
+            spawn_button(root, "Quit", MenuButton::Quit, &text_style);
+        });
+}
+
+pub fn spawn_pause_menu (
+    mut commands: Commands,
+    mut windows: ResMut<Windows>,
+    ui_assets: Res<UiAssets>,
+) {
+    unlock_cursor(&mut windows);
+
+    let text_style = text_style(&ui_assets);
+
+    commands
+        .spawn_bundle(menu_root())
+        .insert(MenuRoot)
+        .with_children(|root| {
+            root.spawn_bundle(TextBundle {
+                text: Text::with_section("Paused", TextStyle { font_size: 48.0, ..text_style.clone() }, default()),
+                style: Style { margin: Rect::all(Val::Px(24.0)), ..default() },
+                ..default()
+            });
+
+            spawn_button(root, "Resume", MenuButton::Resume, &text_style);
+            spawn_button(root, "Settings", MenuButton::Settings, &text_style);
+            spawn_button(root, "Save and Quit to Menu", MenuButton::SaveAndQuit, &text_style);
+            spawn_button(root, "Quit Game", MenuButton::Quit, &text_style);
+        });
+}
+
+pub fn despawn_menus (
+    mut commands: Commands,
+
+    query: Query<Entity, With<MenuRoot>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Typing in the seed on the main menu. Only digits are accepted.
+pub fn seed_input (
+    mut chars: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut seed: ResMut<SeedInput>,
+
+    mut label_query: Query<&mut Text, With<SeedLabel>>,
+) {
+    let mut changed = false;
+
+    for ev in chars.iter() {
+        if ev.char.is_ascii_digit() && seed.len() < 19 {
+            seed.push(ev.char);
+            changed = true;
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        seed.pop();
+        changed = true;
+    }
+
+    if changed {
+        for mut text in label_query.iter_mut() {
+            text.sections[0].value = format!("Seed: {}", **seed);
+        }
+    }
+}
+
+/// Resumes when the quit bind is pressed again while paused.
+pub fn pause_input (
+    mut commands: Commands,
+    inputs: BindInputs,
+    keybinds: Res<Keybinds>,
+    rebind_menu: Res<RebindMenu>,
+) {
+    if !rebind_menu.open && inputs.just_pressed(&keybinds.quit) {
+        commands.insert_resource(NextState(GameState::Playing));
+    }
+}
+
+pub fn menu_buttons (
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    keybinds: Res<Keybinds>,
+    mut rebind_menu: ResMut<RebindMenu>,
+    opt_seed_input: Option<Res<SeedInput>>,
+    opt_world_seed: Option<Res<WorldSeed>>,
+    world_config: Res<WorldConfig>,
+    opt_client: Option<Res<NetClient>>,
+    server_address: Res<ServerAddress>,
+    time: Res<Time>,
+    loaded_chunks: Res<LoadedChunks>,
+
+    mut query: Query<(&Interaction, &MenuButton, &mut UiColor), Changed<Interaction>>,
+    player_query: Query<&Transform, With<Player>>,
+
+    mut ev_exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut color) in query.iter_mut() {
+        match interaction {
+            Interaction::Hovered => {
+                *color = BUTTON_HOVERED_COLOR.into();
+                continue;
+            }
+            Interaction::None => {
+                *color = BUTTON_COLOR.into();
+                continue;
+            }
+            Interaction::Clicked => {}
+        }
+
+        match button {
+            MenuButton::NewWorld => {
+                let seed = opt_seed_input.as_ref().and_then(|seed| seed.parse::<u64>().ok()).unwrap_or(0);
+
+                commands.insert_resource(WorldSeed(seed));
+                commands.insert_resource(WorldConfig::default());
+                commands.remove_resource::<SavedWorld>();
+                commands.insert_resource(NextState(GameState::StartMapGen));
+            }
+            MenuButton::LoadWorld => {
+                if let Some(saved) = SavedWorld::load() {
+                    commands.insert_resource(WorldSeed(saved.seed));
+                    commands.insert_resource(saved);
+                    commands.insert_resource(NextState(GameState::StartMapGen));
+                }
+            }
+            MenuButton::JoinServer => {
+                match NetClient::connect(**server_address, time.seconds_since_startup()) {
+                    Ok(client) => {
+                        commands.insert_resource(client);
+                        commands.insert_resource(NextState(GameState::Connecting));
+                    }
+                    Err(error) => {
+                        warn!("Couldn't open a socket to join {}: {}", **server_address, error);
+                    }
+                }
+            }
+            MenuButton::Settings => {
+                if !rebind_menu.open {
+                    rebind_menu.open = true;
+                    spawn_menu(&mut commands, &keybinds, ui_assets.font.clone());
+                }
+            }
+            MenuButton::Resume => {
+                commands.insert_resource(NextState(GameState::Playing));
+            }
+            MenuButton::SaveAndQuit => {
+                // The server's world isn't ours to save.
+                if opt_client.is_some() {
+                    commands.insert_resource(NextState(GameState::MainMenu));
+                    continue;
+                }
+
+                let seed = opt_world_seed.as_ref().map_or(0, |seed| ***seed);
+                let player_position = player_query.iter().next().map_or(Vec3::ZERO, |transform| transform.translation);
+
+                SavedWorld::new(seed, *world_config, player_position, &loaded_chunks).save();
+                commands.insert_resource(NextState(GameState::MainMenu));
+            }
+            MenuButton::Quit => {
+                ev_exit.send(AppExit);
+            }
+        }
+    }
+}
+
+// Helper functions
+fn unlock_cursor(windows: &mut Windows) {
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_cursor_lock_mode(false);
+        window.set_cursor_visibility(true);
+    }
+}
+
+fn text_style(ui_assets: &UiAssets) -> TextStyle {
+    TextStyle {
+        font: ui_assets.font.clone(),
+        font_size: 24.0,
+        color: Color::WHITE,
+    }
+}
+
+fn menu_root() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+        ..default()
+    }
+}
+
+fn spawn_button(parent: &mut ChildBuilder, label: &str, button: MenuButton, text_style: &TextStyle) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(360.0), Val::Px(48.0)),
+                margin: Rect::all(Val::Px(6.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: BUTTON_COLOR.into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|button| {
+            button.spawn_bundle(TextBundle {
+                text: Text::with_section(label, text_style.clone(), default()),
+                ..default()
+            });
+        });
+}
+
+// Components
+#[derive(Component)]
+pub struct MenuRoot;
+
+#[derive(Component)]
+pub struct SeedLabel;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuButton {
+    NewWorld,
+    LoadWorld,
+    JoinServer,
+    Settings,
+    Resume,
+    SaveAndQuit,
+    Quit,
+}
+
+// Resources
+/// What's been typed into the seed box so far.
+#[derive(Deref, DerefMut, Default)]
+pub struct SeedInput(String);
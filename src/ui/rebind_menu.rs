@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+
+use crate::{actions::Action, keybinds::{Keybinds, BindInputs, Bind}, ui::UiAssets};
+
+
+// Consts
+const MENU_ACTIONS: [Action; 8] = [Action::WalkForward, Action::WalkBackward, Action::StrafeLeft, Action::StrafeRight, Action::Jump, Action::Crouch, Action::Sprint, Action::Mine];
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+
+// Systems
+pub fn toggle_rebind_menu (
+    mut commands: Commands,
+    mut windows: ResMut<Windows>,
+
+    mut menu: ResMut<RebindMenu>,
+    keybinds: Res<Keybinds>,
+    inputs: BindInputs,
+    ui_assets: Res<UiAssets>,
+
+    root_query: Query<Entity, With<RebindMenuRoot>>,
+) {
+    // Whatever gets pressed while listening is a new binding, even if it's the menu key.
+    if menu.listening.is_some() || !inputs.just_pressed(&keybinds.rebind_menu) {
+        return;
+    }
+
+    menu.open = !menu.open;
+
+    if menu.open {
+        let window = windows.get_primary_mut().unwrap();
+        window.set_cursor_lock_mode(false);
+        window.set_cursor_visibility(true);
+
+        spawn_menu(&mut commands, &keybinds, ui_assets.font.clone());
+    }
+    else {
+        for entity in root_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn rebind_buttons (
+    mut menu: ResMut<RebindMenu>,
+
+    mut query: Query<(&Interaction, &RebindButton, &mut UiColor), Changed<Interaction>>,
+) {
+    for (interaction, button, mut color) in query.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {
+                menu.listening = Some(**button);
+            }
+            Interaction::Hovered => {
+                *color = BUTTON_HOVERED_COLOR.into();
+            }
+            Interaction::None => {
+                *color = BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// Should run before rebind_buttons, so the click that starts listening doesn't get bound itself.
+pub fn capture_rebind (
+    mut menu: ResMut<RebindMenu>,
+    mut keybinds: ResMut<Keybinds>,
+    inputs: BindInputs,
+) {
+    let action = match menu.listening {
+        Some(action) => action,
+        None => return,
+    };
+
+    if let Some(bind) = inputs.first_just_pressed() {
+        // Escape cancels rather than binding.
+        if bind != Bind::Key(KeyCode::Escape) {
+            keybinds.rebind(action, bind);
+            keybinds.save();
+        }
+
+        menu.listening = None;
+    }
+}
+
+pub fn update_rebind_labels (
+    menu: Res<RebindMenu>,
+    keybinds: Res<Keybinds>,
+
+    mut query: Query<(&RebindLabel, &mut Text)>,
+) {
+    if !menu.is_changed() && !keybinds.is_changed() {
+        return;
+    }
+
+    for (label, mut text) in query.iter_mut() {
+        text.sections[0].value = if menu.listening == Some(**label) {
+            "Press a key (Esc to cancel)".to_string()
+        }
+        else {
+            describe_binds(keybinds.binds(**label))
+        };
+    }
+}
+
+// Helper functions
+pub fn spawn_menu(commands: &mut Commands, keybinds: &Keybinds, font: Handle<Font>) {
+    let text_style = TextStyle {
+        font,
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+            ..default()
+        })
+        .insert(RebindMenuRoot)
+        .with_children(|root| {
+            for action in MENU_ACTIONS {
+                root
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(600.0), Val::Px(40.0)),
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            margin: Rect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn_bundle(TextBundle {
+                            text: Text::with_section(format!("{:?}", action), text_style.clone(), default()),
+                            ..default()
+                        });
+
+                        row
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(320.0), Val::Px(36.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                color: BUTTON_COLOR.into(),
+                                ..default()
+                            })
+                            .insert(RebindButton(action))
+                            .with_children(|button| {
+                                button
+                                    .spawn_bundle(TextBundle {
+                                        text: Text::with_section(describe_binds(keybinds.binds(action)), text_style.clone(), default()),
+                                        ..default()
+                                    })
+                                    .insert(RebindLabel(action));
+                            });
+                    });
+            }
+        });
+}
+
+fn describe_binds(binds: &[Bind]) -> String {
+    binds.iter().map(|bind| bind.describe()).collect::<Vec<String>>().join(" / ")
+}
+
+// Components
+#[derive(Component)]
+pub struct RebindMenuRoot;
+
+#[derive(Component, Deref)]
+pub struct RebindButton(Action);
+
+#[derive(Component, Deref)]
+pub struct RebindLabel(Action);
+
+// Resources
+#[derive(Default)]
+pub struct RebindMenu {
+    pub open: bool,
+    pub listening: Option<Action>,
+}
@@ -0,0 +1,34 @@
+use bevy::prelude::*;
+
+
+// Plugin
+#[derive(Default)]
+pub struct GameUiPlugin;
+impl Plugin for GameUiPlugin {
+    fn build(&self, app: &mut App) {
+        app
+         .init_resource::<UiAssets>();
+
+    }
+}
+
+// Systems
+pub fn spawn_ui_camera (
+    mut commands: Commands,
+) {
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+// Resources
+pub struct UiAssets {
+    pub font: Handle<Font>,
+}
+impl FromWorld for UiAssets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+
+        Self {
+            font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// A headless app for testing gameplay systems, with no window, renderer or real input.
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use bevy::{prelude::*, ecs::system::CommandQueue, input::InputPlugin, math::Vec3A};
+use iyes_loopless::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use ndarray::Array3;
+
+use zombies_gold::{
+    GameState, SimulationPlugin,
+    actions::{self, Action, AnalogInput, Stance, Stamina, MoveIntent},
+    camera::CameraController,
+    coords::CHUNK_SIZE,
+    keybinds::Keybinds,
+    map::{LoadedChunks, Block, BlockType, SetBlockEvent, SetBlockShape, WorldConfig, WorldSeed},
+    physics::{self, AabbCollider, Velocity, Falls, Submerged, StepTime},
+    player::Player,
+    replay::ReplayPlugin,
+    setup::EYE_HEIGHT,
+    storage::ChunkStorage,
+};
+
+
+// Consts
+/// How many seconds one tick simulates. Every tick is exactly this long, however long it really took to run.
+pub const TICK: f32 = 1.0 / 60.0;
+
+// Data
+pub struct Harness {
+    pub app: App,
+    pub player: Option<Entity>,
+    held: Vec<Action>,
+}
+impl Harness {
+    /// The simulation on its own, already playing, with nothing loaded.
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app
+         .add_plugins(MinimalPlugins)
+         .add_plugin(InputPlugin)
+         .init_resource::<Windows>()
+         .init_resource::<Keybinds>()
+         .init_resource::<AnalogInput>()
+         .insert_resource(WorldSeed(0))
+         .add_loopless_state(GameState::Playing)
+         .add_plugin(SimulationPlugin)
+         .add_plugin(ReplayPlugin)
+         .add_system_to_stage(CoreStage::PreUpdate, fixed_step.after(physics::update_step_time))
+
+         .add_system_set(
+             ConditionSet::new()
+                 .run_in_state(GameState::Playing)
+                 .label("input")
+                 .before("physics")
+                 .with_system(actions::update_stance.before(actions::process_actions))
+                 .with_system(actions::process_actions)
+                 .with_system(actions::edge_protection.after(actions::process_actions))
+                 .into()
+         );
+
+        // The first update sets up the clock and everything the plugins queued.
+        app.update();
+
+        Self { app, player: None, held: Vec::new() }
+    }
+
+    /// Same as `new`, but with a different world height.
+    pub fn with_config(config: WorldConfig) -> Self {
+        let mut harness = Self::new();
+        harness.app.insert_resource(config);
+        harness
+    }
+
+    /// Loads a chunk filled by `fill`, which is given world positions.
+    pub fn load_chunk(&mut self, index: IVec3, fill: impl Fn(IVec3) -> BlockType) {
+        let origin = index * CHUNK_SIZE as i32;
+        let blocks = Array3::from_shape_fn((CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE), |(x, y, z)| {
+            Block::new(fill(origin + IVec3::new(x as i32, y as i32, z as i32)))
+        });
+
+        self.app.world.resource_scope(|world, mut loaded_chunks: Mut<LoadedChunks>| {
+            let mut queue = CommandQueue::default();
+            let mut commands = Commands::new(&mut queue, world);
+            loaded_chunks.load_chunk(index, ChunkStorage::from_dense(&blocks), &mut commands);
+            queue.apply(world);
+        });
+    }
+
+    /// Loads the chunks from `min` to `max` inclusive. Everything below y = 0 is `floor`, everything else is air.
+    pub fn load_flat(&mut self, min: IVec3, max: IVec3, floor: BlockType) {
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    self.load_chunk(IVec3::new(x, y, z), |position| if position.y < 0 {floor} else {BlockType::Air});
+                }
+            }
+        }
+    }
+
+    /// Sends a block edit the same way the game does, and runs it.
+    pub fn set_block(&mut self, position: IVec3, block_type: BlockType) {
+        self.app.world.resource_mut::<Events<SetBlockEvent>>().send(SetBlockEvent {
+            shape: SetBlockShape::Block(position),
+            block: Block::new(block_type),
+        });
+        self.tick();
+    }
+
+    pub fn block(&self, position: IVec3) -> Option<BlockType> {
+        self.app.world.resource::<LoadedChunks>().get_block(position).map(|block| block.block_type())
+    }
+
+    /// A player like the one the game spawns, without the bits that need a renderer.
+    /// `position` is the middle of their collider, so feet on a floor at y = -0.5 means a y of 0.4.
+    pub fn spawn_player(&mut self, position: Vec3) -> Entity {
+        let player = self.app.world
+            .spawn()
+            .insert(ActionState::<Action>::default())
+            .insert(Player)
+            .insert(Falls)
+            .insert(AabbCollider::new(Vec3A::new(0.4, 1.8, 0.4)))
+            .insert(Velocity(Vec3::ZERO))
+            .insert(Submerged::default())
+            .insert(Stance::default())
+            .insert(Stamina::new(100.0))
+            .insert(MoveIntent::default())
+            .insert(Transform::from_translation(position))
+            .insert(GlobalTransform::identity())
+            .with_children(|c| {
+                c.spawn()
+                    .insert(Transform::from_translation(Vec3::new(0.0, EYE_HEIGHT, 0.0)))
+                    .insert(GlobalTransform::identity())
+                    .insert(CameraController::new(EYE_HEIGHT));
+            })
+            .id();
+
+        self.player = Some(player);
+        player
+    }
+
+    /// Holds down exactly these actions from the next tick on, letting go of everything else.
+    pub fn hold(&mut self, actions: &[Action]) {
+        let player = self.player.expect("spawn a player before giving it input");
+        let mut action_state = self.app.world.get_mut::<ActionState<Action>>(player).unwrap();
+
+        for action in self.held.drain(..) {
+            if !actions.contains(&action) {
+                action_state.release(action);
+            }
+        }
+        for action in actions {
+            if !action_state.pressed(*action) {
+                action_state.press(*action);
+            }
+        }
+
+        self.held = actions.to_vec();
+    }
+
+    /// Runs one frame, TICK seconds long.
+    pub fn tick(&mut self) {
+        self.app.update();
+    }
+
+    pub fn run(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    pub fn run_seconds(&mut self, seconds: f32) {
+        self.run((seconds / TICK).round() as usize);
+    }
+
+    pub fn transform(&self) -> Transform {
+        *self.app.world.get::<Transform>(self.player.unwrap()).unwrap()
+    }
+
+    pub fn velocity(&self) -> Vec3 {
+        **self.app.world.get::<Velocity>(self.player.unwrap()).unwrap()
+    }
+
+    /// The player's collider where they are now.
+    pub fn bounds(&self) -> AabbCollider {
+        let aabb = self.app.world.get::<AabbCollider>(self.player.unwrap()).unwrap();
+        AabbCollider::add_location(self.transform().translation, aabb)
+    }
+}
+
+// Systems
+/// Swaps the real frame time for a fixed one, so every run of a test steps the same way.
+fn fixed_step (
+    mut step_time: ResMut<StepTime>,
+) {
+    step_time.set_delta_seconds(TICK);
+}
@@ -0,0 +1,123 @@
+// Runs the simulation headless with scripted input, in fixed ticks.
+
+mod common;
+
+use bevy::prelude::*;
+
+use zombies_gold::{actions::Action, map::{BlockType, WorldConfig}};
+
+use common::Harness;
+
+
+// Consts
+// Where a player's feet end up standing on the default floor, which is dirt below y = 0
+const FLOOR: f32 = -0.5;
+const STANDING: Vec3 = Vec3::new(0.0, FLOOR + 0.9, 0.0);
+// Ticks are fixed, so this only has to cover float rounding
+const TOLERANCE: f32 = 0.001;
+
+// Helper functions
+/// A flat dirt floor spanning the chunks either side of the origin, with a player standing in the middle.
+fn flat_world() -> Harness {
+    let mut harness = Harness::new();
+    harness.load_flat(IVec3::new(-1, -1, -1), IVec3::new(0, 0, 0), BlockType::Dirt);
+    harness.spawn_player(STANDING);
+    harness
+}
+
+// Tests
+#[test]
+fn falling_player_lands_on_floor() {
+    let mut harness = flat_world();
+    let mut transform = harness.app.world.get_mut::<Transform>(harness.player.unwrap()).unwrap();
+    transform.translation.y = 10.0;
+
+    harness.run_seconds(3.0);
+
+    let bounds = harness.bounds();
+    assert!((bounds.min.y - FLOOR).abs() < TOLERANCE, "feet at {} instead of on the floor", bounds.min.y);
+    assert!(harness.velocity().y.abs() < TOLERANCE, "still moving at {}", harness.velocity().y);
+}
+
+#[test]
+fn standing_player_stays_put() {
+    let mut harness = flat_world();
+
+    harness.run_seconds(1.0);
+
+    assert!(harness.transform().translation.distance(STANDING) < TOLERANCE, "drifted to {}", harness.transform().translation);
+}
+
+#[test]
+fn walking_into_wall_stops_player() {
+    let mut harness = flat_world();
+
+    // Forward is -z. The wall's near face is at z = -3.5.
+    for x in -2..=2 {
+        for y in 0..=2 {
+            harness.set_block(IVec3::new(x, y, -4), BlockType::Stone);
+        }
+    }
+
+    harness.hold(&[Action::WalkForward]);
+    harness.run_seconds(3.0);
+
+    let bounds = harness.bounds();
+    assert!(bounds.min.z >= -3.5 - TOLERANCE, "went through the wall to {}", bounds.min.z);
+    assert!(bounds.min.z < -3.0, "never reached the wall, stopped at {}", bounds.min.z);
+    assert!((bounds.min.y - FLOOR).abs() < TOLERANCE, "feet at {} instead of on the floor", bounds.min.y);
+}
+
+#[test]
+fn letting_go_slows_player_down() {
+    let mut harness = flat_world();
+
+    harness.hold(&[Action::StrafeRight]);
+    harness.run_seconds(1.0);
+    let moving = harness.velocity().x;
+
+    harness.hold(&[]);
+    harness.run_seconds(1.0);
+
+    assert!(moving > 1.0, "barely moved, at {}", moving);
+    assert!(harness.velocity().x < moving, "didn't slow down from {}", moving);
+}
+
+#[test]
+fn block_edits_show_up_in_loaded_chunks() {
+    let mut harness = flat_world();
+    let position = IVec3::new(3, 0, 3);
+
+    harness.set_block(position, BlockType::Stone);
+    assert_eq!(harness.block(position), Some(BlockType::Stone));
+
+    harness.set_block(position, BlockType::Air);
+    assert_eq!(harness.block(position), Some(BlockType::Air));
+}
+
+#[test]
+fn edits_outside_world_height_are_ignored() {
+    let mut harness = Harness::with_config(WorldConfig { min_height: -16, max_height: 4, bedrock: true });
+    harness.load_flat(IVec3::new(0, -1, 0), IVec3::new(0, 0, 0), BlockType::Dirt);
+
+    harness.set_block(IVec3::new(1, 4, 1), BlockType::Stone);
+    harness.set_block(IVec3::new(1, 5, 1), BlockType::Stone);
+    // The bedrock floor
+    harness.set_block(IVec3::new(1, -16, 1), BlockType::Air);
+
+    assert_eq!(harness.block(IVec3::new(1, 4, 1)), Some(BlockType::Stone));
+    assert_eq!(harness.block(IVec3::new(1, 5, 1)), Some(BlockType::Air));
+    assert_eq!(harness.block(IVec3::new(1, -16, 1)), Some(BlockType::Dirt));
+}
+
+#[test]
+fn mining_floor_drops_player() {
+    let mut harness = flat_world();
+    harness.run_seconds(0.5);
+
+    harness.set_block(IVec3::new(0, -1, 0), BlockType::Air);
+    harness.run_seconds(1.0);
+
+    let bounds = harness.bounds();
+    assert!((bounds.min.y - (FLOOR - 1.0)).abs() < TOLERANCE, "feet at {} instead of in the hole", bounds.min.y);
+}
@@ -0,0 +1,106 @@
+// Records a scripted run, then plays it back into a fresh copy of the same world.
+
+mod common;
+
+use std::{env, fs, path::{Path, PathBuf}, process};
+
+use bevy::{prelude::*, app::AppExit};
+
+use zombies_gold::{actions::Action, map::BlockType, replay::{Recording, Playback, ReplayOutcome}};
+
+use common::Harness;
+
+
+// Consts
+const START: Vec3 = Vec3::new(0.0, 3.0, 0.0);
+// Plenty for the replays here to finish in
+const MAX_PLAYBACK_TICKS: usize = 1000;
+
+// Helper functions
+/// A flat dirt floor with the player above it, optionally with a stone wall across where the recording walks.
+/// Built without ticking, so the player starts from the same place either way.
+fn world(wall: bool) -> Harness {
+    let mut harness = Harness::new();
+
+    for x in -1..=0 {
+        for y in -1..=0 {
+            for z in -1..=0 {
+                harness.load_chunk(IVec3::new(x, y, z), |position| {
+                    if position.y < 0 {
+                        BlockType::Dirt
+                    }
+                    else if wall && position.z == -2 && position.y <= 2 {
+                        BlockType::Stone
+                    }
+                    else {
+                        BlockType::Air
+                    }
+                });
+            }
+        }
+    }
+
+    harness.spawn_player(START);
+    harness
+}
+
+fn replay_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("zombies_gold_{}_{}.replay", name, process::id()))
+}
+
+/// Falls, walks, sprints and strafes around, then writes the recording out the way quitting the game does.
+fn record(path: &Path) {
+    let mut harness = world(false);
+    harness.app.insert_resource(Recording::new(path.to_path_buf()));
+
+    harness.run_seconds(1.0);
+    harness.hold(&[Action::WalkForward]);
+    harness.run_seconds(0.5);
+    harness.hold(&[Action::WalkForward, Action::Sprint]);
+    harness.run_seconds(0.5);
+    harness.hold(&[Action::StrafeLeft]);
+    harness.run_seconds(0.5);
+    harness.hold(&[]);
+    harness.run_seconds(0.5);
+
+    harness.app.world.resource_mut::<Events<AppExit>>().send(AppExit);
+    harness.tick();
+}
+
+/// Plays the recording back until it's finished, and returns how it went.
+fn play_back(mut harness: Harness, path: &Path) -> ReplayOutcome {
+    let playback = Playback::load(path).expect("couldn't load the recording");
+    harness.app.insert_resource(playback);
+
+    for _ in 0..MAX_PLAYBACK_TICKS {
+        harness.tick();
+        if let Some(outcome) = harness.app.world.get_resource::<ReplayOutcome>() {
+            return *outcome;
+        }
+    }
+
+    panic!("replay didn't finish within {} ticks", MAX_PLAYBACK_TICKS);
+}
+
+// Tests
+#[test]
+fn unchanged_run_matches_recording() {
+    let path = replay_path("unchanged");
+    record(&path);
+
+    let outcome = play_back(world(false), &path);
+    fs::remove_file(&path).ok();
+
+    assert_eq!(outcome.diverged, None);
+}
+
+#[test]
+fn changed_world_diverges() {
+    let path = replay_path("changed");
+    record(&path);
+
+    let outcome = play_back(world(true), &path);
+    fs::remove_file(&path).ok();
+
+    assert!(outcome.diverged.is_some(), "walked through a wall and still matched");
+}